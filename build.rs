@@ -0,0 +1,226 @@
+use std::collections::HashSet;
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+const CLDR_DIR: &str = "data/cldr";
+
+const CLDR_FILENAME: &str = "annotations.json";
+
+/// Annotations for sequences like keycaps and people in particular skin tones,
+/// which CLDR derives from the others. Locales don't need to have these.
+const CLDR_DERIVED_FILENAME: &str = "annotationsDerived.json";
+
+const CLDR_RS_FILENAME: &str = "cldr_annotations.rs";
+
+const LATEX_FILENAME: &str = "data/latex_symbols.txt";
+
+const LATEX_RS_FILENAME: &str = "latex_symbols.rs";
+
+type Annotation = (String, String, Vec<String>);
+
+/// Adds the given character to the given text as it can be typed on a US
+/// keyboard, which means dropping any accents, spelling out ligatures and
+/// using the normal form of full-width characters, e.g. `a` for both `ä` and
+/// `ａ`. Other characters are added as-is.
+fn push_folded_for_typing(text: &mut String, ch: char) {
+    let folded = match ch {
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(ch as u32 - 0xFEE0).unwrap_or(ch),
+        '\u{3000}' => ' ',
+        '\u{DF}' => return text.push_str("ss"),
+        '\u{E6}' => return text.push_str("ae"),
+        '\u{153}' => return text.push_str("oe"),
+        _ if ch.is_ascii() => ch,
+        _ => match unicode_names2::name(ch).map(|name| name.to_string()) {
+            Some(name) if name.starts_with("COMBINING ") => return,
+            // Accented letters are named after the letter, e.g. `LATIN SMALL
+            // LETTER A WITH DIAERESIS`.
+            Some(name) => name
+                .split_once(" WITH ")
+                .and_then(|(base, _)| unicode_names2::character(base))
+                .unwrap_or(ch),
+            None => ch,
+        },
+    };
+    text.push(folded);
+}
+
+fn format_for_command_name(value: &str) -> String {
+    let value = value
+        .to_lowercase()
+        .replace(
+            ['\u{201C}', '\u{201D}', '\u{201E}', '\u{AB}', '\u{BB}'],
+            "\"",
+        )
+        .replace('\u{2019}', "'");
+    let mut name = String::new();
+    for ch in value.chars() {
+        push_folded_for_typing(&mut name, ch);
+    }
+    name
+}
+
+/// Reads a CLDR annotations file in the format used by cldr-json, adding each
+/// character that isn't already there along with its short name and keywords.
+fn read_annotations(path: &Path, annotations: &mut Vec<Annotation>, seen: &mut HashSet<String>) {
+    println!("cargo:rerun-if-changed={}", path.display());
+    let text = fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("Unable to read {}: {}", path.display(), e));
+    let data: Value = serde_json::from_str(&text)
+        .unwrap_or_else(|e| panic!("Unable to parse {}: {}", path.display(), e));
+    let entries = data["annotations"]["annotations"]
+        .as_object()
+        .or_else(|| data["annotationsDerived"]["annotations"].as_object());
+    let Some(entries) = entries else {
+        panic!("{} doesn't contain any annotations", path.display());
+    };
+    for (ch, item) in entries {
+        if seen.contains(ch) {
+            continue;
+        }
+        // Some characters only have keywords, which we can't name them by.
+        let Some(tts) = item["tts"][0].as_str() else {
+            continue;
+        };
+        let name = format_for_command_name(tts);
+        let keywords = item["default"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(format_for_command_name)
+            .filter(|keyword| *keyword != name)
+            .collect();
+        seen.insert(ch.clone());
+        annotations.push((ch.clone(), name, keywords));
+    }
+}
+
+fn write_cldr_annotations(out_dir: &Path) {
+    println!("cargo:rerun-if-changed={}", CLDR_DIR);
+
+    let mut locales: Vec<(String, PathBuf)> = fs::read_dir(CLDR_DIR)
+        .unwrap_or_else(|e| panic!("Unable to read {}: {}", CLDR_DIR, e))
+        .map(|entry| entry.unwrap().path().join(CLDR_FILENAME))
+        .filter(|path| path.exists())
+        .map(|path| {
+            let locale = path.parent().unwrap().file_name().unwrap();
+            (locale.to_string_lossy().into_owned(), path)
+        })
+        .collect();
+    locales.sort();
+
+    let mut rs =
+        String::from("// This file was generated by build.rs from the CLDR data in data/cldr.\n\n");
+    let mut compiled = vec![];
+    for (locale, path) in &locales {
+        let mut annotations = vec![];
+        let mut seen = HashSet::new();
+        read_annotations(path, &mut annotations, &mut seen);
+        let derived_path = path.with_file_name(CLDR_DERIVED_FILENAME);
+        if derived_path.exists() {
+            read_annotations(&derived_path, &mut annotations, &mut seen);
+        }
+        // Characters are inserted by typing their names, so a locale whose
+        // names mostly can't be typed on a US keyboard, even with their
+        // accents dropped, e.g. Japanese, would only take up space.
+        let typable = annotations
+            .iter()
+            .filter(|(_, name, _)| name.is_ascii())
+            .count();
+        if typable * 2 < annotations.len() {
+            println!(
+                "cargo:warning=Leaving out the {} CLDR locale, since most of its names can't be typed.",
+                locale
+            );
+            continue;
+        }
+        compiled.push(locale);
+        writeln!(
+            rs,
+            "const ANNOTATIONS_{}: [Annotation; {}] = [",
+            locale.to_uppercase().replace('-', "_"),
+            annotations.len()
+        )
+        .unwrap();
+        for (ch, name, keywords) in annotations {
+            writeln!(rs, "    ({:?}, {:?}, &{:?}),", ch, name, keywords).unwrap();
+        }
+        rs.push_str("];\n\n");
+    }
+    writeln!(
+        rs,
+        "pub const LOCALES: [(&str, &[Annotation]); {}] = [",
+        compiled.len()
+    )
+    .unwrap();
+    for locale in compiled {
+        writeln!(
+            rs,
+            "    ({:?}, &ANNOTATIONS_{}),",
+            locale,
+            locale.to_uppercase().replace('-', "_")
+        )
+        .unwrap();
+    }
+    rs.push_str("];\n");
+
+    fs::write(out_dir.join(CLDR_RS_FILENAME), rs).unwrap();
+}
+
+/// Parses a code point like `U+03B1`.
+fn parse_code_point(value: &str) -> Option<char> {
+    let hex = value.strip_prefix("U+")?;
+    char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+}
+
+/// Writes out the LaTeX symbol table, where each line names a command and the
+/// code points it stands for, e.g. `alpha U+03B1`.
+fn write_latex_symbols(out_dir: &Path) {
+    println!("cargo:rerun-if-changed={}", LATEX_FILENAME);
+
+    let text = fs::read_to_string(LATEX_FILENAME)
+        .unwrap_or_else(|e| panic!("Unable to read {}: {}", LATEX_FILENAME, e));
+    let mut symbols = vec![];
+    for (idx, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        let name = parts.next().unwrap();
+        let symbol: Option<String> = parts.map(parse_code_point).collect();
+        match symbol {
+            Some(symbol) if !symbol.is_empty() => symbols.push((name.to_string(), symbol)),
+            _ => panic!(
+                "Invalid code point on line {} of {}",
+                idx + 1,
+                LATEX_FILENAME
+            ),
+        }
+    }
+    symbols.sort();
+
+    let mut rs =
+        String::from("// This file was generated by build.rs from data/latex_symbols.txt.\n\n");
+    writeln!(
+        rs,
+        "pub const LATEX_SYMBOLS: [(&str, &str); {}] = [",
+        symbols.len()
+    )
+    .unwrap();
+    for (name, symbol) in symbols {
+        writeln!(rs, "    ({:?}, {:?}),", name, symbol).unwrap();
+    }
+    rs.push_str("];\n");
+
+    fs::write(out_dir.join(LATEX_RS_FILENAME), rs).unwrap();
+}
+
+fn main() {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    write_cldr_annotations(&out_dir);
+    write_latex_symbols(&out_dir);
+}
@@ -0,0 +1,24 @@
+#!/bin/sh
+# Replaces the annotations in this directory with the ones from the release
+# of cldr-json below, for every locale that's already here or given as an
+# argument, e.g. `./update.sh es`. Every locale should come from the same
+# release, so bump the version here rather than fetching one locale from
+# another release.
+
+set -e
+
+CLDR_JSON_VERSION=44.1.0
+
+cd "$(dirname "$0")"
+BASE_URL="https://raw.githubusercontent.com/unicode-org/cldr-json/$CLDR_JSON_VERSION/cldr-json"
+
+LOCALES=$( (ls -d */ | tr -d /; printf '%s\n' "$@") | sort -u)
+
+for LOCALE in $LOCALES; do
+  echo "Fetching $LOCALE."
+  mkdir -p "$LOCALE"
+  curl -sSf -o "$LOCALE/annotations.json" \
+    "$BASE_URL/cldr-annotations-full/annotations/$LOCALE/annotations.json"
+  curl -sSf -o "$LOCALE/annotationsDerived.json" \
+    "$BASE_URL/cldr-annotations-derived-full/annotationsDerived/$LOCALE/annotations.json"
+done
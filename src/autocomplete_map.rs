@@ -56,8 +56,10 @@ fn get_matches(input: &str, name: &str) -> Vec<Range<usize>> {
     // though, just in case we decide to change the implementation again
     // in the future.
 
+    // Case is ignored, since typed input keeps it. Only ASCII letters are
+    // folded, so that the match's byte offsets stay the same in the name.
     if input.len() > 0 {
-        if let Some(start) = name.find(input) {
+        if let Some(start) = name.to_ascii_lowercase().find(&input.to_ascii_lowercase()) {
             return vec![start..(start + input.len())];
         }
     }
@@ -65,7 +67,7 @@ fn get_matches(input: &str, name: &str) -> Vec<Range<usize>> {
     vec![]
 }
 
-fn get_best_candidates<'a, I: Iterator<Item = &'a String>>(
+fn get_best_candidates<'a, S: AsRef<str> + ?Sized + 'a, I: Iterator<Item = &'a S>>(
     input: &str,
     names: I,
     max_results: usize,
@@ -73,12 +75,10 @@ fn get_best_candidates<'a, I: Iterator<Item = &'a String>>(
     let mut candidates: Vec<CandidateSuggestion> = Vec::new();
 
     for name in names {
-        let matches = get_matches(input, name.as_str());
+        let name = name.as_ref();
+        let matches = get_matches(input, name);
         if !matches.is_empty() {
-            candidates.push(CandidateSuggestion {
-                name: name.as_str(),
-                matches,
-            });
+            candidates.push(CandidateSuggestion { name, matches });
         }
     }
 
@@ -87,6 +87,20 @@ fn get_best_candidates<'a, I: Iterator<Item = &'a String>>(
     candidates
 }
 
+/// Autocompletes the given input using the given names, returning the best
+/// matches along with where they match. This is useful for names that aren't
+/// kept in an `AutocompleteMap`.
+pub fn autocomplete_names<'a, S: AsRef<str> + ?Sized + 'a, I: Iterator<Item = &'a S>>(
+    input: &str,
+    names: I,
+    max_results: usize,
+) -> Vec<(&'a str, Vec<Range<usize>>)> {
+    get_best_candidates(input, names, max_results)
+        .into_iter()
+        .map(|candidate| (candidate.name, candidate.matches))
+        .collect()
+}
+
 pub struct AutocompleteMap<T: Clone> {
     entries: HashMap<String, T>,
 }
@@ -106,6 +120,10 @@ impl<T: Clone> AutocompleteMap<T> {
         self.entries.remove(name.as_ref())
     }
 
+    pub fn get<U: AsRef<str>>(&self, name: U) -> Option<&T> {
+        self.entries.get(name.as_ref())
+    }
+
     pub fn contains<U: AsRef<str>>(&self, name: U) -> bool {
         self.entries.contains_key(name.as_ref())
     }
@@ -114,9 +132,25 @@ impl<T: Clone> AutocompleteMap<T> {
         &self,
         input: U,
         max_results: usize,
+    ) -> Vec<AutocompleteSuggestion<T>> {
+        self.autocomplete_filtered(input, max_results, |_| true)
+    }
+
+    /// Like `autocomplete()`, but only considers entries whose values satisfy
+    /// the given predicate.
+    pub fn autocomplete_filtered<U: AsRef<str>, P: Fn(&T) -> bool>(
+        &self,
+        input: U,
+        max_results: usize,
+        predicate: P,
     ) -> Vec<AutocompleteSuggestion<T>> {
         let mut results: Vec<AutocompleteSuggestion<T>> = Vec::with_capacity(max_results);
-        let candidates = get_best_candidates(input.as_ref(), self.entries.keys(), max_results);
+        let names = self
+            .entries
+            .iter()
+            .filter(|(_, value)| predicate(value))
+            .map(|(name, _)| name);
+        let candidates = get_best_candidates(input.as_ref(), names, max_results);
 
         for candidate in candidates.iter() {
             let name = String::from(candidate.name);
@@ -170,6 +204,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_autocomplete_filtered_works() {
+        let mut am = AutocompleteMap::new();
+        am.insert("boop", 1);
+        am.insert("boink", 2);
+        assert_eq!(
+            am.autocomplete_filtered("bo", 500, |value| *value == 1),
+            vec![sugg("boop", vec![0..2], 1)]
+        );
+    }
+
     #[test]
     fn test_get_best_candidates_ignores_nonmatches() {
         assert_eq!(
@@ -218,6 +263,12 @@ mod tests {
         assert_eq!(get_matches("boop", "boop"), vec![0..4]);
     }
 
+    #[test]
+    fn test_get_matches_ignores_case() {
+        assert_eq!(get_matches("Boop", "goboop"), vec![2..6]);
+        assert_eq!(get_matches("boop", "BOOP"), vec![0..4]);
+    }
+
     #[test]
     fn test_get_matches_returns_contiguous_matches() {
         assert_eq!(get_matches("popper", "party popper"), vec![6..12]);
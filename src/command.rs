@@ -1,21 +1,199 @@
 use super::error::Error;
+use super::prompt::Prompt;
+use super::system::{
+    get_foreground_executable_path, get_foreground_window_name, get_selected_text,
+};
 use super::ui::UserInterface;
 use dyn_clone::DynClone;
+use std::ops::Range;
+use std::sync::{Arc, OnceLock};
 
+/// Information about what the user is currently doing, which commands can use
+/// to decide whether they're available. Clones share the selection, so a task
+/// can be given a clone to fetch it on its worker thread.
+#[derive(Clone)]
+pub struct CommandContext {
+    foreground_executable_path: Option<String>,
+    foreground_window_name: Option<String>,
+    selection: Arc<OnceLock<Option<String>>>,
+    fetch_selection: fn() -> Option<String>,
+}
+
+impl CommandContext {
+    pub fn new(
+        foreground_executable_path: Option<String>,
+        foreground_window_name: Option<String>,
+        selection: Option<String>,
+    ) -> Self {
+        CommandContext {
+            foreground_executable_path,
+            foreground_window_name,
+            selection: Arc::new(OnceLock::from(selection)),
+            fetch_selection: || None,
+        }
+    }
+
+    /// Returns the context of whatever application is in the foreground.
+    pub fn current() -> Self {
+        CommandContext {
+            foreground_executable_path: get_foreground_executable_path().ok(),
+            foreground_window_name: get_foreground_window_name().ok(),
+            selection: Arc::new(OnceLock::new()),
+            fetch_selection: || get_selected_text().ok().flatten(),
+        }
+    }
+
+    pub fn foreground_executable_path(&self) -> Option<&str> {
+        self.foreground_executable_path.as_deref()
+    }
+
+    pub fn foreground_window_name(&self) -> Option<&str> {
+        self.foreground_window_name.as_deref()
+    }
+
+    /// Returns the text selected in the foreground application, if any.
+    ///
+    /// Because this involves copying the selection to the clipboard, it's only
+    /// fetched the first time it's asked for. That can take a moment, so it's
+    /// best done from a task.
+    pub fn selection(&self) -> Option<&str> {
+        self.selection
+            .get_or_init(|| (self.fetch_selection)())
+            .as_deref()
+    }
+}
+
+impl Default for CommandContext {
+    fn default() -> Self {
+        CommandContext::new(None, None, None)
+    }
+}
+
+/// What a command produced when it ran. Rather than performing side effects
+/// directly, commands can return one of these and have the UI perform it,
+/// which makes them easier to test and compose.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandOutput {
+    /// The command did everything it needed to do on its own.
+    Nothing,
+    /// Text that should be inserted into the current application.
+    InsertText(String),
+    /// A message that should be shown to the user.
+    ShowMessage(String),
+    /// A URL or filesystem path that should be opened.
+    Open(String),
+    /// Text that should be copied to the clipboard.
+    CopyText(String),
+    /// A follow-up question for the user to answer.
+    Prompt(Prompt),
+}
+
+impl CommandOutput {
+    /// Returns the text this output represents, if any.
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            CommandOutput::InsertText(text)
+            | CommandOutput::ShowMessage(text)
+            | CommandOutput::CopyText(text) => Some(text),
+            CommandOutput::Open(_) | CommandOutput::Prompt(_) | CommandOutput::Nothing => None,
+        }
+    }
+}
+
+impl CommandOutput {
+    /// Records which plugin produced the output, if it's a prompt, so that
+    /// the prompt's choices are run on the plugin's behalf.
+    pub fn with_plugin(self, plugin: Option<String>) -> Self {
+        match self {
+            CommandOutput::Prompt(prompt) => CommandOutput::Prompt(prompt.with_plugin(plugin)),
+            output => output,
+        }
+    }
+}
+
+impl From<()> for CommandOutput {
+    fn from(_: ()) -> Self {
+        CommandOutput::Nothing
+    }
+}
+
+/// A possible argument for a command, e.g. the name of a character.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArgumentSuggestion {
+    pub argument: String,
+    /// The parts of the argument that match what the user typed.
+    pub matches: Vec<Range<usize>>,
+    /// Something to show next to the argument in the menu, e.g. the character
+    /// it names.
+    pub label: Option<String>,
+}
+
+#[allow(unused_variables)]
 pub trait Command: DynClone {
     fn name(&self) -> String;
-    fn execute(&mut self, ui: &mut UserInterface) -> Result<(), Error>;
+    fn execute(&mut self, ui: &mut UserInterface) -> Result<CommandOutput, Error>;
+
+    /// Whether the command makes sense in the given context. Unavailable
+    /// commands aren't suggested or run.
+    fn is_available(&self, context: &CommandContext) -> bool {
+        true
+    }
+
+    /// Returns a short description of what running the command with the given
+    /// argument would do, e.g. the character it would insert. This is called
+    /// as the user types, so it should be quick.
+    fn preview(&self, argument: Option<&str>) -> Option<String> {
+        None
+    }
+
+    /// Whether the command can be given an argument, either typed after its
+    /// name (e.g. `copy hello`) or piped in from a previous command.
+    fn takes_argument(&self) -> bool {
+        false
+    }
+
+    fn execute_with_argument(
+        &mut self,
+        ui: &mut UserInterface,
+        argument: &str,
+    ) -> Result<CommandOutput, Error> {
+        Err(Error::new(format!(
+            "The \u{201C}{}\u{201D} command doesn't take an argument.",
+            self.name()
+        )))
+    }
+
+    /// Returns the best arguments for the command that match what the user
+    /// has typed so far. This is called as the user types, so it should be
+    /// quick.
+    fn suggest_arguments(
+        &self,
+        partial_argument: &str,
+        max_results: usize,
+    ) -> Vec<ArgumentSuggestion> {
+        vec![]
+    }
+
+    /// Whether the arguments the command suggests are characters, e.g. emoji,
+    /// which are shown in a grid rather than a list.
+    fn suggests_characters(&self) -> bool {
+        false
+    }
 }
 
 dyn_clone::clone_trait_object!(Command);
 
 #[derive(Clone)]
-pub struct SimpleCommand<F: FnMut(&mut UserInterface) -> Result<(), Error> + Clone> {
+pub struct SimpleCommand<F> {
     name_: String,
     execute_: F,
 }
 
-impl<F: FnMut(&mut UserInterface) -> Result<(), Error> + Clone> SimpleCommand<F> {
+impl<F, R> SimpleCommand<F>
+where
+    F: FnMut(&mut UserInterface) -> Result<R, Error> + Clone,
+    R: Into<CommandOutput>,
+{
     pub fn new<T: Into<String>>(name: T, execute: F) -> Self {
         SimpleCommand {
             name_: name.into(),
@@ -28,13 +206,17 @@ impl<F: FnMut(&mut UserInterface) -> Result<(), Error> + Clone> SimpleCommand<F>
     }
 }
 
-impl<F: FnMut(&mut UserInterface) -> Result<(), Error> + Clone> Command for SimpleCommand<F> {
+impl<F, R> Command for SimpleCommand<F>
+where
+    F: FnMut(&mut UserInterface) -> Result<R, Error> + Clone,
+    R: Into<CommandOutput>,
+{
     fn name(&self) -> String {
         self.name_.clone()
     }
 
-    fn execute(&mut self, ui: &mut UserInterface) -> Result<(), Error> {
-        (self.execute_)(ui)
+    fn execute(&mut self, ui: &mut UserInterface) -> Result<CommandOutput, Error> {
+        Ok((self.execute_)(ui)?.into())
     }
 }
 
@@ -47,3 +229,20 @@ fn test_simple_command_works() {
     .into_box();
     let _cmd2 = cmd.clone();
 }
+
+#[test]
+fn test_command_output_text_works() {
+    assert_eq!(CommandOutput::InsertText("a".into()).text(), Some("a"));
+    assert_eq!(CommandOutput::ShowMessage("b".into()).text(), Some("b"));
+    assert_eq!(CommandOutput::Open("c".into()).text(), None);
+    assert_eq!(CommandOutput::from(()), CommandOutput::Nothing);
+}
+
+#[test]
+fn test_command_context_works() {
+    let context = CommandContext::new(Some("boop.exe".into()), None, Some("hi".into()));
+    assert_eq!(context.foreground_executable_path(), Some("boop.exe"));
+    assert_eq!(context.foreground_window_name(), None);
+    assert_eq!(context.selection(), Some("hi"));
+    assert_eq!(CommandContext::default().selection(), None);
+}
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+
+use super::autocomplete_map::{AutocompleteMap, AutocompleteSuggestion};
+use super::command::Command;
+
+/// Separates the name of a plugin from the name of one of its commands, e.g.
+/// `hotkeys: copy`.
+pub const NAMESPACE_SEPARATOR: &str = ": ";
+
+/// Returns the name of the given command that includes the plugin it belongs
+/// to, if any.
+pub fn qualify_name(plugin: Option<&str>, name: &str) -> String {
+    match plugin {
+        Some(plugin) => format!("{}{}{}", plugin, NAMESPACE_SEPARATOR, name),
+        None => name.to_string(),
+    }
+}
+
+/// A command, along with the plugin that added it, if any.
+#[derive(Clone)]
+pub struct RegisteredCommand {
+    pub plugin: Option<String>,
+    pub command: Box<dyn Command>,
+}
+
+/// Keeps track of every command, and decides what to do when several plugins
+/// add commands with the same name.
+///
+/// Any command can be invoked by its qualified name, e.g. `hotkeys: copy`,
+/// and is normally listed in the menu under its plain name, e.g. `copy`. If
+/// several plugins add a command with the same name, only one of them gets
+/// the plain name and the rest are listed under their qualified names. The
+/// winner is a command that doesn't belong to any plugin if there is one,
+/// then the preferred plugin's command, then whichever was added first.
+pub struct CommandRegistry {
+    commands: AutocompleteMap<RegisteredCommand>,
+    /// The qualified names of the commands with each plain name, in the
+    /// order they were added.
+    qualified_names: HashMap<String, Vec<String>>,
+    /// Maps the names listed in the menu to qualified names.
+    menu: AutocompleteMap<String>,
+    /// Which plugin should win when several plugins add a command with the
+    /// given name.
+    preferred_plugins: HashMap<String, String>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        CommandRegistry {
+            commands: AutocompleteMap::new(),
+            qualified_names: HashMap::new(),
+            menu: AutocompleteMap::new(),
+            preferred_plugins: HashMap::new(),
+        }
+    }
+
+    /// Adds the given command, unless its plugin already has a command with
+    /// the same name, returning whether it was added.
+    pub fn add(&mut self, plugin: Option<String>, command: Box<dyn Command>) -> bool {
+        let name = command.name();
+        let qualified_name = qualify_name(plugin.as_deref(), &name);
+        if self.commands.contains(&qualified_name) {
+            return false;
+        }
+        self.commands.insert(
+            qualified_name.clone(),
+            RegisteredCommand { plugin, command },
+        );
+        self.qualified_names
+            .entry(name.clone())
+            .or_default()
+            .push(qualified_name);
+        self.update_menu(&name);
+        true
+    }
+
+    pub fn remove(&mut self, plugin: Option<&str>, name: &str) -> Option<Box<dyn Command>> {
+        let qualified_name = qualify_name(plugin, name);
+        let registered = self.commands.remove(&qualified_name)?;
+        self.menu.remove(&qualified_name);
+        self.menu.remove(name);
+        if let Some(qualified_names) = self.qualified_names.get_mut(name) {
+            qualified_names.retain(|other| *other != qualified_name);
+            if qualified_names.is_empty() {
+                self.qualified_names.remove(name);
+            }
+        }
+        self.update_menu(name);
+        Some(registered.command)
+    }
+
+    /// Removes every command that belongs to the given plugin.
+    pub fn remove_plugin(&mut self, plugin: &str) {
+        let names: Vec<String> = self
+            .qualified_names
+            .iter()
+            .filter(|(name, qualified_names)| {
+                qualified_names.contains(&qualify_name(Some(plugin), name))
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in names {
+            self.remove(Some(plugin), &name);
+        }
+    }
+
+    pub fn contains(&self, plugin: Option<&str>, name: &str) -> bool {
+        self.commands.contains(qualify_name(plugin, name))
+    }
+
+    /// Finds the command with the given name, which can either be the name
+    /// it's listed under in the menu or its qualified name.
+    pub fn get(&self, name: &str) -> Option<&RegisteredCommand> {
+        match self.menu.get(name) {
+            Some(qualified_name) => self.commands.get(qualified_name),
+            None => self.commands.get(name),
+        }
+    }
+
+    /// Sets which plugin should win when several plugins add a command with
+    /// each of the given names.
+    pub fn set_preferred_plugins(&mut self, preferred_plugins: HashMap<String, String>) {
+        let mut names: Vec<String> = self.preferred_plugins.keys().cloned().collect();
+        names.extend(preferred_plugins.keys().cloned());
+        self.preferred_plugins = preferred_plugins;
+        for name in names {
+            self.update_menu(&name);
+        }
+    }
+
+    /// Relists every command with the given name in the menu.
+    fn update_menu(&mut self, name: &str) {
+        self.menu.remove(name);
+        let Some(qualified_names) = self.qualified_names.get(name) else {
+            return;
+        };
+        let plugins: Vec<Option<&str>> = qualified_names
+            .iter()
+            .map(|qualified_name| {
+                self.commands
+                    .get(qualified_name)
+                    .and_then(|registered| registered.plugin.as_deref())
+            })
+            .collect();
+        let preferred_plugin = self.preferred_plugins.get(name).map(String::as_str);
+        let winner = plugins
+            .iter()
+            .position(Option::is_none)
+            .or_else(|| {
+                plugins
+                    .iter()
+                    .position(|plugin| preferred_plugin.is_some() && *plugin == preferred_plugin)
+            })
+            .unwrap_or(0);
+        for (idx, qualified_name) in qualified_names.iter().enumerate() {
+            self.menu.remove(qualified_name);
+            let menu_name = if idx == winner {
+                name.to_string()
+            } else {
+                qualified_name.clone()
+            };
+            self.menu.insert(menu_name, qualified_name.clone());
+        }
+    }
+
+    /// Autocompletes the given input using the names listed in the menu, or
+    /// qualified names if the input names a plugin. Only commands satisfying
+    /// the given predicate are considered.
+    pub fn autocomplete_filtered<P: Fn(&dyn Command) -> bool>(
+        &self,
+        input: &str,
+        max_results: usize,
+        predicate: P,
+    ) -> Vec<AutocompleteSuggestion<RegisteredCommand>> {
+        if input.contains(NAMESPACE_SEPARATOR) {
+            return self
+                .commands
+                .autocomplete_filtered(input, max_results, |registered| {
+                    predicate(registered.command.as_ref())
+                });
+        }
+        self.menu
+            .autocomplete_filtered(input, max_results, |qualified_name| {
+                self.commands
+                    .get(qualified_name)
+                    .is_some_and(|registered| predicate(registered.command.as_ref()))
+            })
+            .into_iter()
+            .filter_map(|sugg| {
+                Some(AutocompleteSuggestion {
+                    value: self.commands.get(&sugg.value)?.clone(),
+                    name: sugg.name,
+                    matches: sugg.matches,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{CommandOutput, SimpleCommand};
+
+    fn make_command(name: &str) -> Box<dyn Command> {
+        Box::new(SimpleCommand::new(name, |_ui| Ok(CommandOutput::Nothing)))
+    }
+
+    fn add(registry: &mut CommandRegistry, plugin: Option<&str>, name: &str) -> bool {
+        registry.add(plugin.map(String::from), make_command(name))
+    }
+
+    fn plugin_of(registry: &CommandRegistry, name: &str) -> Option<Option<String>> {
+        registry
+            .get(name)
+            .map(|registered| registered.plugin.clone())
+    }
+
+    fn menu_names(registry: &CommandRegistry, input: &str) -> Vec<String> {
+        let mut names: Vec<String> = registry
+            .autocomplete_filtered(input, 10, |_| true)
+            .into_iter()
+            .map(|sugg| sugg.name)
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_conflicts_are_listed_with_qualified_names() {
+        let mut registry = CommandRegistry::new();
+        assert!(add(&mut registry, Some("builtin"), "copy"));
+        assert!(add(&mut registry, Some("hotkeys"), "copy"));
+        assert!(!add(&mut registry, Some("hotkeys"), "copy"));
+        assert_eq!(menu_names(&registry, "copy"), vec!["copy", "hotkeys: copy"]);
+        assert_eq!(plugin_of(&registry, "copy"), Some(Some("builtin".into())));
+        assert_eq!(
+            plugin_of(&registry, "builtin: copy"),
+            Some(Some("builtin".into()))
+        );
+        assert_eq!(
+            plugin_of(&registry, "hotkeys: copy"),
+            Some(Some("hotkeys".into()))
+        );
+    }
+
+    #[test]
+    fn test_qualified_names_are_autocompleted_when_plugin_is_named() {
+        let mut registry = CommandRegistry::new();
+        add(&mut registry, Some("hotkeys"), "copy");
+        assert_eq!(menu_names(&registry, "copy"), vec!["copy"]);
+        assert_eq!(menu_names(&registry, "hotkeys: co"), vec!["hotkeys: copy"]);
+    }
+
+    #[test]
+    fn test_preferred_plugins_win_conflicts() {
+        let mut registry = CommandRegistry::new();
+        add(&mut registry, Some("builtin"), "copy");
+        add(&mut registry, Some("hotkeys"), "copy");
+        let mut preferred_plugins = HashMap::new();
+        preferred_plugins.insert(String::from("copy"), String::from("hotkeys"));
+        registry.set_preferred_plugins(preferred_plugins);
+        assert_eq!(menu_names(&registry, "copy"), vec!["builtin: copy", "copy"]);
+        assert_eq!(plugin_of(&registry, "copy"), Some(Some("hotkeys".into())));
+    }
+
+    #[test]
+    fn test_removing_winner_promotes_next_command() {
+        let mut registry = CommandRegistry::new();
+        add(&mut registry, Some("builtin"), "copy");
+        add(&mut registry, Some("hotkeys"), "copy");
+        add(&mut registry, Some("builtin"), "quit");
+        registry.remove_plugin("builtin");
+        assert_eq!(menu_names(&registry, "copy"), vec!["copy"]);
+        assert_eq!(plugin_of(&registry, "copy"), Some(Some("hotkeys".into())));
+        assert!(registry.get("quit").is_none());
+        assert!(registry.get("builtin: copy").is_none());
+    }
+
+    #[test]
+    fn test_commands_without_plugins_always_win() {
+        let mut registry = CommandRegistry::new();
+        add(&mut registry, Some("hotkeys"), "cancel boop");
+        add(&mut registry, None, "cancel boop");
+        assert_eq!(
+            menu_names(&registry, "cancel"),
+            vec!["cancel boop", "hotkeys: cancel boop"]
+        );
+        assert_eq!(plugin_of(&registry, "cancel boop"), Some(None));
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::command_registry::NAMESPACE_SEPARATOR;
+use super::error::Error;
+use super::system::{get_enso_home_dir, InsertionMethod, DEFAULT_PASTE_DELAY_MS};
+
+const CONFIG_FILENAME: &str = "config.txt";
+
+const DISABLED_PLUGIN_KEY: &str = "disabled_plugin";
+
+/// Settings with this key decide which plugin's command wins when several
+/// plugins have commands with the same name, e.g. `preferred_command =
+/// hotkeys: copy`.
+const PREFERRED_COMMAND_KEY: &str = "preferred_command";
+
+/// Settings with this key make Enso insert text into the given application by
+/// pasting it rather than typing it, e.g. `paste_into = putty.exe`.
+const PASTE_INTO_KEY: &str = "paste_into";
+
+/// The setting with this key decides how many milliseconds applications are
+/// given to paste text before the clipboard is restored, e.g.
+/// `paste_delay_ms = 1000`.
+const PASTE_DELAY_KEY: &str = "paste_delay_ms";
+
+/// Settings with this key choose which languages characters can be inserted
+/// by name in, most preferred first, e.g. `character_locale = de`.
+const CHARACTER_LOCALE_KEY: &str = "character_locale";
+
+/// The setting with this key decides which skin tone emoji are inserted in,
+/// e.g. `skin_tone = medium`.
+const SKIN_TONE_KEY: &str = "skin_tone";
+
+/// The setting with this key decides whether emoji of people are inserted as
+/// a woman, a man or a gender-neutral person, e.g. `gender = woman`.
+const GENDER_KEY: &str = "gender";
+
+/// Enso's configuration, which lives in `~/.enso/config.txt`.
+///
+/// Each line is a `key = value` setting, and some keys can be given more than
+/// once. Blank lines and lines starting with `#` are ignored, and are
+/// preserved when Enso changes the file.
+#[derive(Debug, Default)]
+pub struct Config {
+    path: Option<PathBuf>,
+    lines: Vec<String>,
+}
+
+impl Config {
+    /// Loads the configuration file, if there is one.
+    pub fn load() -> Result<Self, Error> {
+        let mut path = get_enso_home_dir()?;
+        path.push(CONFIG_FILENAME);
+        let mut config = if path.exists() {
+            println!("Loading configuration from \"{}\".", path.display());
+            Config::parse(&std::fs::read_to_string(&path)?)
+        } else {
+            Config::default()
+        };
+        config.path = Some(path);
+        Ok(config)
+    }
+
+    pub fn parse(text: &str) -> Self {
+        Config {
+            path: None,
+            lines: text.lines().map(String::from).collect(),
+        }
+    }
+
+    fn parse_line(line: &str) -> Option<(&str, &str)> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (key, value) = line.split_once('=')?;
+        Some((key.trim(), value.trim()))
+    }
+
+    fn settings(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.lines
+            .iter()
+            .filter_map(|line| Config::parse_line(line))
+    }
+
+    /// Returns every value given for the given key, in order.
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        self.settings()
+            .filter(|(k, _)| *k == key)
+            .map(|(_, value)| value)
+            .collect()
+    }
+
+    /// Returns descriptions of any lines that couldn't be understood.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+        for (idx, line) in self.lines.iter().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            match Config::parse_line(line) {
+                None => warnings.push(format!("No \"=\" found on line {}: {}", idx + 1, line)),
+                Some((PREFERRED_COMMAND_KEY, value)) if !value.contains(NAMESPACE_SEPARATOR) => {
+                    warnings.push(format!(
+                        "Expected \"<plugin>{}<command>\" on line {}: {}",
+                        NAMESPACE_SEPARATOR,
+                        idx + 1,
+                        line
+                    ))
+                }
+                Some((PASTE_DELAY_KEY, value)) if value.parse::<u64>().is_err() => {
+                    warnings.push(format!(
+                        "Expected a number of milliseconds on line {}: {}",
+                        idx + 1,
+                        line
+                    ))
+                }
+                Some(_) => {}
+            }
+        }
+        warnings
+    }
+
+    /// Returns which plugin should win when several plugins have a command
+    /// with each name.
+    pub fn get_preferred_plugins(&self) -> HashMap<String, String> {
+        self.get_all(PREFERRED_COMMAND_KEY)
+            .into_iter()
+            .filter_map(|value| value.split_once(NAMESPACE_SEPARATOR))
+            .map(|(plugin, name)| (name.trim().to_string(), plugin.trim().to_string()))
+            .collect()
+    }
+
+    /// Returns how text should be inserted into the application with the
+    /// given executable, which can be given as a full path.
+    pub fn get_insertion_method(&self, executable_path: &str) -> InsertionMethod {
+        let filename = executable_path
+            .rsplit(['\\', '/'])
+            .next()
+            .unwrap_or(executable_path);
+        let should_paste = self.get_all(PASTE_INTO_KEY).iter().any(|value| {
+            value.eq_ignore_ascii_case(filename) || value.eq_ignore_ascii_case(executable_path)
+        });
+        if should_paste {
+            InsertionMethod::Paste(self.get_paste_delay())
+        } else {
+            InsertionMethod::Type
+        }
+    }
+
+    /// Returns how long applications are given to paste text before the
+    /// clipboard is restored.
+    pub fn get_paste_delay(&self) -> Duration {
+        let ms = self
+            .get_all(PASTE_DELAY_KEY)
+            .pop()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_PASTE_DELAY_MS);
+        Duration::from_millis(ms)
+    }
+
+    pub fn get_character_locales(&self) -> Vec<&str> {
+        self.get_all(CHARACTER_LOCALE_KEY)
+    }
+
+    /// Returns the most recent skin tone setting, if there is one.
+    pub fn get_skin_tone(&self) -> Option<&str> {
+        self.get_all(SKIN_TONE_KEY).pop()
+    }
+
+    /// Returns the most recent gender setting, if there is one.
+    pub fn get_gender(&self) -> Option<&str> {
+        self.get_all(GENDER_KEY).pop()
+    }
+
+    pub fn is_plugin_disabled(&self, name: &str) -> bool {
+        self.get_all(DISABLED_PLUGIN_KEY).contains(&name)
+    }
+
+    /// Changes whether the given plugin is disabled, saving the configuration
+    /// file if there is one.
+    pub fn set_plugin_disabled(&mut self, name: &str, disabled: bool) -> Result<(), Error> {
+        if self.is_plugin_disabled(name) == disabled {
+            return Ok(());
+        }
+        if disabled {
+            self.lines
+                .push(format!("{} = {}", DISABLED_PLUGIN_KEY, name));
+        } else {
+            self.lines
+                .retain(|line| Config::parse_line(line) != Some((DISABLED_PLUGIN_KEY, name)));
+        }
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        if let Some(path) = &self.path {
+            let mut text = self.lines.join("\n");
+            text.push('\n');
+            std::fs::write(path, text)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_all_works() {
+        let config = Config::parse("# hi\n\nboop = 1\nblah=2\n  boop =  3  ");
+        assert_eq!(config.get_all("boop"), vec!["1", "3"]);
+        assert_eq!(config.get_all("blah"), vec!["2"]);
+        assert!(config.get_all("nope").is_empty());
+    }
+
+    #[test]
+    fn test_warnings_works() {
+        let config = Config::parse("# hi\nboop = 1\nblarg");
+        assert_eq!(
+            config.warnings(),
+            vec![String::from("No \"=\" found on line 3: blarg")]
+        );
+    }
+
+    #[test]
+    fn test_get_preferred_plugins_works() {
+        let config = Config::parse("preferred_command = hotkeys: copy\npreferred_command = copy");
+        let mut expected = HashMap::new();
+        expected.insert(String::from("copy"), String::from("hotkeys"));
+        assert_eq!(config.get_preferred_plugins(), expected);
+        assert_eq!(
+            config.warnings(),
+            vec![String::from(
+                "Expected \"<plugin>: <command>\" on line 2: preferred_command = copy"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_get_insertion_method_works() {
+        let config = Config::parse("paste_into = PuTTY.exe");
+        assert_eq!(
+            config.get_insertion_method("C:\\Program Files\\PuTTY\\putty.exe"),
+            InsertionMethod::Paste(Duration::from_millis(DEFAULT_PASTE_DELAY_MS))
+        );
+        assert_eq!(
+            config.get_insertion_method("C:\\Windows\\notepad.exe"),
+            InsertionMethod::Type
+        );
+    }
+
+    #[test]
+    fn test_get_paste_delay_works() {
+        let config = Config::parse("paste_into = putty.exe\npaste_delay_ms = 1000");
+        assert_eq!(
+            config.get_insertion_method("putty.exe"),
+            InsertionMethod::Paste(Duration::from_millis(1000))
+        );
+        let config = Config::parse("paste_delay_ms = soon");
+        assert_eq!(
+            config.get_paste_delay(),
+            Duration::from_millis(DEFAULT_PASTE_DELAY_MS)
+        );
+        assert_eq!(
+            config.warnings(),
+            vec![String::from(
+                "Expected a number of milliseconds on line 1: paste_delay_ms = soon"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_set_plugin_disabled_works() {
+        let mut config = Config::parse("# My plugins\ndisabled_plugin = scripts");
+        assert!(config.is_plugin_disabled("scripts"));
+        assert!(!config.is_plugin_disabled("hotkeys"));
+
+        config.set_plugin_disabled("hotkeys", true).unwrap();
+        assert!(config.is_plugin_disabled("hotkeys"));
+
+        config.set_plugin_disabled("scripts", false).unwrap();
+        assert!(!config.is_plugin_disabled("scripts"));
+        assert_eq!(
+            config.lines,
+            vec!["# My plugins", "disabled_plugin = hotkeys"]
+        );
+    }
+}
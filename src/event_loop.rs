@@ -146,9 +146,11 @@ impl EventLoop {
                             // other safe Rust-based synchronization mechanisms.
                         }
                         WM_TIMER => {
-                            // Do nothing. It seems like DirectX or the GDI
-                            // or something sends these as a result of our
-                            // layered window code, and I'm not sure why.
+                            // Do nothing. The UI uses these to wake up when a
+                            // preview is due, which happens in `loop_cb`. It
+                            // also seems like DirectX or the GDI or something
+                            // sends these as a result of our layered window
+                            // code, and I'm not sure why.
                         }
                         _ => {
                             println!("Unknown thread message: 0x{:x}", msg.message);
@@ -0,0 +1,127 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A direction the selection can be moved in with the arrow keys.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// Where the entries of a menu go when they're shown in a grid that's filled
+/// a row at a time, which is how suggested characters like emoji are shown.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GridLayout {
+    len: usize,
+    columns: usize,
+}
+
+impl GridLayout {
+    pub fn new(len: usize, columns: usize) -> Self {
+        GridLayout {
+            len,
+            columns: columns.max(1),
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.len.div_ceil(self.columns)
+    }
+
+    /// Returns the row and column of the given entry.
+    pub fn position(&self, idx: usize) -> (usize, usize) {
+        (idx / self.columns, idx % self.columns)
+    }
+
+    /// Returns where the top-left corner of the given entry's cell goes,
+    /// relative to the top-left corner of the grid.
+    pub fn cell_origin(&self, idx: usize, cell_size: f32) -> (f32, f32) {
+        let (row, column) = self.position(idx);
+        (column as f32 * cell_size, row as f32 * cell_size)
+    }
+
+    /// Returns the width and height of the whole grid.
+    pub fn size(&self, cell_size: f32) -> (f32, f32) {
+        let columns = self.len.min(self.columns);
+        (columns as f32 * cell_size, self.rows() as f32 * cell_size)
+    }
+
+    /// Returns the entry that moving the selection from the given entry in
+    /// the given direction selects. Moving off an edge wraps around to the
+    /// other side, and left and right wrap to the previous and next rows.
+    pub fn move_selection(&self, idx: usize, direction: Direction) -> usize {
+        if self.len == 0 {
+            return 0;
+        }
+        let (_, column) = self.position(idx);
+        match direction {
+            Direction::Left if idx == 0 => self.len - 1,
+            Direction::Left => idx - 1,
+            Direction::Right => (idx + 1) % self.len,
+            Direction::Down if idx + self.columns < self.len => idx + self.columns,
+            Direction::Down => column,
+            Direction::Up if idx >= self.columns => idx - self.columns,
+            Direction::Up => {
+                // The bottom row may not reach this far, in which case the
+                // row above it does.
+                let bottom = (self.rows() - 1) * self.columns + column;
+                if bottom < self.len {
+                    bottom
+                } else {
+                    bottom - self.columns
+                }
+            }
+        }
+    }
+}
+
+/// Returns what to show in a grid cell for the given label, which is its
+/// first user-perceived character, so that a label like `☃ (snow)` shows the
+/// character it's about.
+pub fn get_cell_text(label: &str) -> &str {
+    label.graphemes(true).next().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_works() {
+        assert_eq!(GridLayout::new(25, 10).size(2.0), (20.0, 6.0));
+        assert_eq!(GridLayout::new(3, 10).size(2.0), (6.0, 2.0));
+        assert_eq!(GridLayout::new(25, 10).cell_origin(13, 2.0), (6.0, 2.0));
+    }
+
+    #[test]
+    fn test_move_selection_works_within_grid() {
+        let grid = GridLayout::new(25, 10);
+        assert_eq!(grid.move_selection(13, Direction::Up), 3);
+        assert_eq!(grid.move_selection(13, Direction::Down), 23);
+        assert_eq!(grid.move_selection(13, Direction::Left), 12);
+        assert_eq!(grid.move_selection(13, Direction::Right), 14);
+        assert_eq!(grid.move_selection(9, Direction::Right), 10);
+    }
+
+    #[test]
+    fn test_move_selection_wraps_around() {
+        let grid = GridLayout::new(25, 10);
+        assert_eq!(grid.move_selection(0, Direction::Left), 24);
+        assert_eq!(grid.move_selection(24, Direction::Right), 0);
+        assert_eq!(grid.move_selection(3, Direction::Up), 23);
+        assert_eq!(grid.move_selection(7, Direction::Up), 17);
+        assert_eq!(grid.move_selection(17, Direction::Down), 7);
+        assert_eq!(grid.move_selection(23, Direction::Down), 3);
+    }
+
+    #[test]
+    fn test_get_cell_text_works() {
+        assert_eq!(get_cell_text("\u{2603} (snow)"), "\u{2603}");
+        assert_eq!(
+            get_cell_text("\u{1F44B}\u{1F3FD} (pinned)"),
+            "\u{1F44B}\u{1F3FD}"
+        );
+        assert_eq!(get_cell_text(""), "");
+    }
+}
@@ -7,8 +7,9 @@ use winapi::um::processthreadsapi::GetCurrentThreadId;
 use winapi::um::winuser::{GetMessageA, PostThreadMessageA, WM_QUIT};
 
 use winapi::um::winuser::{
-    CallNextHookEx, SetWindowsHookExA, UnhookWindowsHookEx, KBDLLHOOKSTRUCT, VK_CAPITAL,
-    WH_KEYBOARD_LL, WM_KEYDOWN, WM_KEYUP, WM_SYSKEYDOWN, WM_SYSKEYUP,
+    CallNextHookEx, SetWindowsHookExA, UnhookWindowsHookEx, KBDLLHOOKSTRUCT, LLKHF_INJECTED,
+    VK_CAPITAL, VK_LSHIFT, VK_RSHIFT, VK_SHIFT, WH_KEYBOARD_LL, WM_KEYDOWN, WM_KEYUP,
+    WM_SYSKEYDOWN, WM_SYSKEYUP,
 };
 
 use winapi::shared::ntdef::NULL;
@@ -19,7 +20,9 @@ use super::windows_util;
 
 #[derive(Debug)]
 pub enum HookEvent {
-    Keypress(i32),
+    /// A key was pressed in the quasimode. Contains the virtual key code and
+    /// whether the shift key was down at the time.
+    Keypress(i32, bool),
     QuasimodeStart,
     QuasimodeEnd,
 }
@@ -28,6 +31,7 @@ struct HookState {
     sender: Sender<HookEvent>,
     receiver_thread_id: u32,
     in_quasimode: bool,
+    is_shift_down: bool,
 }
 
 impl HookState {
@@ -41,6 +45,10 @@ impl HookState {
         let is_key_down = wm_type == WM_KEYDOWN || wm_type == WM_SYSKEYDOWN;
         let mut force_eat_key = false;
 
+        if vk_code == VK_SHIFT || vk_code == VK_LSHIFT || vk_code == VK_RSHIFT {
+            self.is_shift_down = is_key_down;
+        }
+
         let possible_event: Option<HookEvent> = if self.in_quasimode {
             if is_quasimode_key {
                 if is_key_up {
@@ -52,7 +60,7 @@ impl HookState {
                     None
                 }
             } else if is_key_down {
-                Some(HookEvent::Keypress(vk_code))
+                Some(HookEvent::Keypress(vk_code, self.is_shift_down))
             } else {
                 None
             }
@@ -105,6 +113,7 @@ impl KeyboardHook {
                 sender,
                 receiver_thread_id,
                 in_quasimode: false,
+                is_shift_down: false,
             });
         });
         init_sender.send(unsafe { GetCurrentThreadId() }).unwrap();
@@ -187,6 +196,14 @@ unsafe extern "system" fn hook_callback(n_code: i32, w_param: usize, l_param: is
         CallNextHookEx(null_mut(), n_code, w_param, l_param)
     } else {
         let info = l_param as *const KBDLLHOOKSTRUCT;
+        if (*info).flags & LLKHF_INJECTED != 0
+            && (*info).dwExtraInfo == windows_util::ENSO_KEY_EVENT_MARKER
+        {
+            // Enso synthesized this keystroke itself (e.g. to copy the current
+            // selection), so it shouldn't be treated as input. Keystrokes
+            // synthesized by other programs are still handled as usual.
+            return CallNextHookEx(null_mut(), n_code, w_param, l_param);
+        }
         let vk_code = (*info).vkCode as i32;
         let wm_type = w_param as u32;
         let eat_key: bool = HOOK_STATE.with(|s| match *s.borrow_mut() {
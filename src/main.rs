@@ -6,13 +6,20 @@ extern crate winapi;
 
 mod autocomplete_map;
 mod command;
+mod command_registry;
+mod config;
 mod directx;
 mod error;
 mod event_loop;
+mod grid;
 mod keyboard_hook;
 mod menu;
+mod panics;
+mod pipeline;
 mod plugins;
+mod prompt;
 mod system;
+mod task;
 mod transparent_window;
 mod ui;
 mod windows_util;
@@ -20,6 +27,8 @@ mod windows_util;
 fn run_enso() -> Result<(), Box<error::Error>> {
     use std::sync::mpsc::channel;
 
+    panics::install_hook();
+
     let d3d_device = directx::Direct3DDevice::new()?;
     println!(
         "Created Direct3D device with feature level 0x{:x}.",
@@ -32,10 +41,13 @@ fn run_enso() -> Result<(), Box<error::Error>> {
     windows_util::disable_caps_lock()?;
 
     let keyhook = keyboard_hook::KeyboardHook::install(tx, eloop.get_thread_id());
-    let mut ui = ui::UserInterface::new(d3d_device)?;
+    let mut ui = ui::UserInterface::new(d3d_device, eloop.get_thread_id())?;
 
-    for plugin in plugins::get_all_plugins() {
-        ui.add_plugin(plugin)?;
+    if let Err(error) = ui.load_plugins(plugins::get_all_plugins) {
+        ui.show_message(format!(
+            "An error occurred when loading plugins:\n{}",
+            error
+        ))?;
     }
 
     if !ui.is_showing_message() {
@@ -43,12 +55,19 @@ fn run_enso() -> Result<(), Box<error::Error>> {
     }
 
     println!("Starting Enso.");
-    println!("To exit, hold down CAPS LOCK and type 'QUIT'.");
+    println!("To exit, hold down CAPS LOCK, type 'QUIT', then answer 'yes'.");
+
+    let result = eloop.run(|| ui.process_event_receiver(&rx));
 
-    eloop.run(|| ui.process_event_receiver(&rx))?;
+    // Even if something went wrong, make sure we don't leave the keyboard
+    // hook installed.
+    let shutdown_result = ui.shutdown();
 
     keyhook.uninstall();
 
+    result?;
+    shutdown_result?;
+
     println!("Farewell.");
 
     Ok(())
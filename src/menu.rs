@@ -1,11 +1,26 @@
 use std::convert::TryFrom;
 
+use super::grid::{Direction, GridLayout};
+
 pub struct Menu<T> {
     entries: Vec<T>,
     selected_idx: usize,
+    /// How the entries are laid out if they're shown in a grid rather than
+    /// a list.
+    grid: Option<GridLayout>,
 }
 
 impl<T> Menu<T> {
+    /// Shows the entries in a grid with the given number of columns.
+    pub fn with_grid(mut self, columns: usize) -> Self {
+        self.grid = Some(GridLayout::new(self.entries.len(), columns));
+        self
+    }
+
+    pub fn grid(&self) -> Option<&GridLayout> {
+        self.grid.as_ref()
+    }
+
     pub fn into_selected_entry(mut self) -> T {
         self.entries.remove(self.selected_idx)
     }
@@ -37,6 +52,19 @@ impl<T> Menu<T> {
             self.selected_idx = self.entries.len() - 1;
         }
     }
+
+    /// Moves the selection in the given direction, e.g. in response to an
+    /// arrow key. Lists only move up and down.
+    pub fn move_selection(&mut self, direction: Direction) {
+        match (&self.grid, direction) {
+            (Some(grid), _) => {
+                self.selected_idx = grid.move_selection(self.selected_idx, direction)
+            }
+            (None, Direction::Up) => self.select_prev(),
+            (None, Direction::Down) => self.select_next(),
+            (None, _) => {}
+        }
+    }
 }
 
 impl<T> TryFrom<Vec<T>> for Menu<T> {
@@ -49,6 +77,7 @@ impl<T> TryFrom<Vec<T>> for Menu<T> {
             Ok(Menu {
                 entries,
                 selected_idx: 0,
+                grid: None,
             })
         }
     }
@@ -78,6 +107,21 @@ fn test_it_works_with_filled_vec() {
     assert_eq!(menu.into_selected_entry(), 1);
 }
 
+#[test]
+fn test_move_selection_works() {
+    let mut menu = Menu::try_from(vec![1, 2, 3]).unwrap();
+    menu.move_selection(Direction::Up);
+    assert_eq!(menu.selected_idx, 2);
+    menu.move_selection(Direction::Right);
+    assert_eq!(menu.selected_idx, 2);
+
+    let mut menu = Menu::try_from(vec![1, 2, 3, 4, 5]).unwrap().with_grid(2);
+    menu.move_selection(Direction::Down);
+    assert_eq!(menu.selected_idx, 2);
+    menu.move_selection(Direction::Right);
+    assert_eq!(*menu.selected_entry(), 4);
+}
+
 #[test]
 fn test_try_from_fails_with_empty_vec() {
     let menu = Menu::<usize>::try_from(vec![]);
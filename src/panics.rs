@@ -0,0 +1,111 @@
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::fmt;
+use std::io::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::error::Error;
+use super::system::get_enso_home_dir;
+
+const PANIC_LOG_FILENAME: &str = "panics.log";
+
+thread_local! {
+    /// Details about the most recent panic on this thread, filled in by our
+    /// panic hook, since they're only available while the panic is starting.
+    static LAST_PANIC_DETAILS: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// A panic that was caught by `catch_panic`.
+#[derive(Debug)]
+pub struct CaughtPanic {
+    pub message: String,
+    /// Where the panic happened and how we got there, if known.
+    pub details: Option<String>,
+}
+
+impl fmt::Display for CaughtPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Installs a panic hook that remembers where panics happen, so they can be
+/// logged once they're caught. Panics are still printed to stderr as usual.
+pub fn install_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let location = info
+            .location()
+            .map(|location| location.to_string())
+            .unwrap_or_else(|| String::from("unknown location"));
+        let details = format!("at {}\n{}", location, Backtrace::force_capture());
+        LAST_PANIC_DETAILS.with(|last| *last.borrow_mut() = Some(details));
+        default_hook(info);
+    }));
+}
+
+fn get_payload_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("unknown panic")
+    }
+}
+
+/// Calls the given function, catching any panic that happens while it runs.
+///
+/// Whatever the function was working on may be left in an inconsistent
+/// state, so callers should stop using it if it keeps panicking.
+pub fn catch_panic<T, F: FnOnce() -> T>(f: F) -> Result<T, CaughtPanic> {
+    LAST_PANIC_DETAILS.with(|last| last.borrow_mut().take());
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| CaughtPanic {
+        message: get_payload_message(payload.as_ref()),
+        details: LAST_PANIC_DETAILS.with(|last| last.borrow_mut().take()),
+    })
+}
+
+/// Appends the given panic to `~/.enso/panics.log`, noting what was running
+/// when it happened.
+pub fn log_panic(culprit: &str, panic: &CaughtPanic) -> Result<(), Error> {
+    let mut path = get_enso_home_dir()?;
+    path.push(PANIC_LOG_FILENAME);
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    writeln!(
+        file,
+        "[{}] {} panicked: {}\n{}\n",
+        timestamp,
+        culprit,
+        panic.message,
+        panic.details.as_deref().unwrap_or("No details available.")
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catch_panic_returns_result_when_nothing_panics() {
+        assert_eq!(catch_panic(|| 5).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_catch_panic_catches_panics() {
+        install_hook();
+        let panic = catch_panic(|| panic!("oof {}", 1)).unwrap_err();
+        assert_eq!(panic.message, "oof 1");
+        assert!(panic.details.unwrap().contains("panics.rs"));
+    }
+}
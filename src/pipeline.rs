@@ -0,0 +1,685 @@
+use std::ops::Range;
+
+use super::autocomplete_map::AutocompleteSuggestion;
+use super::command::{Command, CommandContext, CommandOutput};
+use super::command_registry::{CommandRegistry, RegisteredCommand};
+use super::error::Error;
+use super::ui::UserInterface;
+
+/// The character that separates the stages of a pipeline, e.g.
+/// `insert snowman | copy`.
+pub const PIPE: char = '|';
+
+const STAGE_SEPARATOR: &str = " | ";
+
+/// A command along with the argument it was given, if any.
+#[derive(Clone)]
+pub struct Invocation {
+    pub command: Box<dyn Command>,
+    /// The plugin that added the command, if any.
+    pub plugin: Option<String>,
+    pub argument: Option<String>,
+    /// Something to show next to the argument in the menu, e.g. the character
+    /// it names.
+    pub argument_label: Option<String>,
+}
+
+impl Invocation {
+    /// Figures out which command the given text invokes. This is either the
+    /// exact name of a command, or the name of a command that takes an
+    /// argument followed by a space and the argument. Command names may be
+    /// qualified with the plugin they belong to, e.g. `hotkeys: copy`.
+    /// Commands that aren't available in the given context are ignored, and
+    /// the case of their names doesn't matter, though an argument's does.
+    pub fn parse(commands: &CommandRegistry, context: &CommandContext, text: &str) -> Option<Self> {
+        let get = |name: &str| {
+            commands
+                .get(name)
+                .or_else(|| commands.get(&name.to_ascii_lowercase()))
+                .filter(|registered| registered.command.is_available(context))
+        };
+        let text = text.trim();
+        if let Some(registered) = get(text) {
+            return Some(Invocation::new(registered.clone(), None));
+        }
+        // Try the longest possible command names first.
+        for (idx, _) in text.rmatch_indices(' ') {
+            if let Some(registered) = get(&text[..idx]) {
+                if registered.command.takes_argument() {
+                    let argument = text[idx + 1..].trim_start().to_string();
+                    return Some(Invocation::new(registered.clone(), Some(argument)));
+                }
+            }
+        }
+        None
+    }
+
+    /// Like `parse()`, but only finds commands that are being given an
+    /// argument. A command's name followed by a space counts, with an empty
+    /// argument, since that's how its argument starts being typed.
+    fn parse_partial(
+        commands: &CommandRegistry,
+        context: &CommandContext,
+        text: &str,
+    ) -> Option<Self> {
+        let mut invocation = Invocation::parse(commands, context, text)?;
+        if invocation.argument.is_none() {
+            if !text.ends_with(' ') || !invocation.command.takes_argument() {
+                return None;
+            }
+            invocation.argument = Some(String::new());
+        }
+        Some(invocation)
+    }
+
+    fn new(registered: RegisteredCommand, argument: Option<String>) -> Self {
+        Invocation {
+            command: registered.command,
+            plugin: registered.plugin,
+            argument,
+            argument_label: None,
+        }
+    }
+
+    fn execute(
+        &mut self,
+        ui: &mut UserInterface,
+        piped_text: Option<String>,
+    ) -> Result<CommandOutput, Error> {
+        let command = &mut self.command;
+        let argument = &self.argument;
+        ui.run_command_code(self.plugin.clone(), |ui| match (argument, piped_text) {
+            (Some(_), Some(_)) => Err(Error::new(
+                "It was given an argument, but text was also piped into it.",
+            )),
+            (Some(argument), None) => command.execute_with_argument(ui, argument),
+            (None, Some(text)) => command.execute_with_argument(ui, &text),
+            (None, None) => command.execute(ui),
+        })
+    }
+}
+
+/// One or more invocations, where the text output by each one is given as
+/// the argument to the next.
+#[derive(Clone)]
+pub struct Pipeline {
+    stages: Vec<Invocation>,
+}
+
+impl Pipeline {
+    /// Returns a pipeline that just runs the given command, which belongs to
+    /// the given plugin, if any.
+    pub fn single(command: Box<dyn Command>, plugin: Option<String>) -> Self {
+        Pipeline {
+            stages: vec![Invocation {
+                command,
+                plugin,
+                argument: None,
+                argument_label: None,
+            }],
+        }
+    }
+
+    pub fn run(&mut self, ui: &mut UserInterface) -> Result<CommandOutput, Error> {
+        if self.stages.len() == 1 {
+            return self.stages[0].execute(ui, None);
+        }
+        let last_idx = self.stages.len() - 1;
+        let mut piped_text = None;
+        let mut output = CommandOutput::Nothing;
+        for (idx, stage) in self.stages.iter_mut().enumerate() {
+            let stage_name = format!(
+                "Stage {} (\u{201C}{}\u{201D})",
+                idx + 1,
+                stage.command.name()
+            );
+            output = stage
+                .execute(ui, piped_text.take())
+                .map_err(|err| Error::new(format!("{} failed:\n{}", stage_name, err)))?;
+            if idx < last_idx {
+                match output.text() {
+                    Some(text) => piped_text = Some(text.to_string()),
+                    None => {
+                        return Err(Error::new(format!(
+                            "{} didn't produce any text to pass along.",
+                            stage_name
+                        )))
+                    }
+                }
+            }
+        }
+        Ok(output)
+    }
+
+    /// Returns a preview of what the pipeline would do. Only single-stage
+    /// pipelines can be previewed, since later stages depend on the output
+    /// of earlier ones.
+    pub fn preview(&self) -> Option<String> {
+        match self.stages.as_slice() {
+            [stage] => stage.command.preview(stage.argument.as_deref()),
+            _ => None,
+        }
+    }
+
+    /// Returns what to show next to the pipeline's name in the menu, if
+    /// anything.
+    pub fn label(&self) -> Option<&str> {
+        self.stages
+            .last()
+            .and_then(|stage| stage.argument_label.as_deref())
+    }
+
+    /// Returns the argument given to the pipeline's final stage, if any.
+    pub fn final_argument(&self) -> Option<&str> {
+        self.stages
+            .last()
+            .and_then(|stage| stage.argument.as_deref())
+    }
+
+    /// Returns the plugin that added the command in the pipeline's final
+    /// stage, if any.
+    pub fn final_plugin(&self) -> Option<String> {
+        self.stages.last().and_then(|stage| stage.plugin.clone())
+    }
+}
+
+/// Autocompletes the given input, which may consist of several stages
+/// separated by pipes. Only the final stage is autocompleted; the earlier
+/// ones must already invoke commands.
+pub fn autocomplete(
+    commands: &CommandRegistry,
+    context: &CommandContext,
+    input: &str,
+    max_results: usize,
+) -> Vec<AutocompleteSuggestion<Pipeline>> {
+    autocomplete_stages(commands, context, input, max_results, true)
+}
+
+/// Like `autocomplete`, but only suggests arguments for the command in the
+/// final stage, leaving out other commands whose names match, e.g. `insert
+/// digraph` for `insert d`. This is what's shown in a grid of characters.
+pub fn autocomplete_arguments(
+    commands: &CommandRegistry,
+    context: &CommandContext,
+    input: &str,
+    max_results: usize,
+) -> Vec<AutocompleteSuggestion<Pipeline>> {
+    autocomplete_stages(commands, context, input, max_results, false)
+}
+
+fn autocomplete_stages(
+    commands: &CommandRegistry,
+    context: &CommandContext,
+    input: &str,
+    max_results: usize,
+    suggest_names: bool,
+) -> Vec<AutocompleteSuggestion<Pipeline>> {
+    let mut stage_texts: Vec<&str> = input.split(PIPE).collect();
+    let last_text = stage_texts
+        .pop()
+        .expect("split() always yields at least one item")
+        .trim_start();
+    let mut earlier_stages = Vec::with_capacity(stage_texts.len());
+    for text in stage_texts.iter() {
+        match Invocation::parse(commands, context, text) {
+            Some(invocation) => earlier_stages.push(invocation),
+            None => return vec![],
+        }
+    }
+    let prefix: String = stage_texts
+        .iter()
+        .map(|text| format!("{}{}", text.trim(), STAGE_SEPARATOR))
+        .collect();
+
+    let mut last_stages: Vec<(String, Vec<Range<usize>>, Invocation)> = vec![];
+    // A command's name followed by a space asks for suggestions for its
+    // argument before any of it has been typed, e.g. recently used ones.
+    let suggesting_invocation = Invocation::parse_partial(commands, context, last_text);
+    let argument_invocation = suggesting_invocation
+        .clone()
+        .filter(|invocation| invocation.argument.as_deref() != Some(""));
+    let max_named_results = if argument_invocation.is_some() {
+        max_results.saturating_sub(1)
+    } else {
+        max_results
+    };
+    if suggest_names {
+        let suggs = commands.autocomplete_filtered(last_text, max_named_results, |command| {
+            command.is_available(context)
+        });
+        for sugg in suggs {
+            let invocation = Invocation::new(sugg.value, None);
+            last_stages.push((sugg.name, sugg.matches, invocation));
+        }
+    }
+    if let Some(invocation) = &suggesting_invocation {
+        let text = last_text.trim();
+        let argument = invocation.argument.as_deref().unwrap_or_default();
+        // This includes the space(s) between the command's name and its argument.
+        let command_text = if argument.is_empty() {
+            format!("{} ", text)
+        } else {
+            text[..text.len() - argument.len()].to_string()
+        };
+        for sugg in invocation
+            .command
+            .suggest_arguments(argument, max_named_results)
+        {
+            let mut matches = vec![0..command_text.len()];
+            for range in sugg.matches {
+                matches.push((range.start + command_text.len())..(range.end + command_text.len()));
+            }
+            let mut suggested_invocation = invocation.clone();
+            suggested_invocation.argument = Some(sugg.argument.clone());
+            suggested_invocation.argument_label = sugg.label;
+            let name = format!("{}{}", command_text, sugg.argument);
+            last_stages.push((name, matches, suggested_invocation));
+        }
+        last_stages.truncate(max_named_results);
+    }
+    if let Some(invocation) = argument_invocation {
+        let name = last_text.trim_end().to_string();
+        // If the argument was suggested, that suggestion already covers it.
+        if !last_stages.iter().any(|(other, _, _)| *other == name) {
+            let matches = vec![0..name.len()];
+            last_stages.push((name, matches, invocation));
+        }
+    }
+
+    last_stages
+        .into_iter()
+        .map(|(name, matches, invocation)| {
+            let mut all_matches = vec![];
+            if !prefix.is_empty() {
+                all_matches.push(0..prefix.len());
+            }
+            for range in matches {
+                all_matches.push((range.start + prefix.len())..(range.end + prefix.len()));
+            }
+            let mut stages = earlier_stages.clone();
+            stages.push(invocation);
+            AutocompleteSuggestion {
+                name: format!("{}{}", prefix, name),
+                matches: all_matches,
+                value: Pipeline { stages },
+            }
+        })
+        .collect()
+}
+
+/// Whether the final stage of the given input is giving an argument to a
+/// command whose suggestions are characters, which are best shown in a grid.
+pub fn suggests_characters(
+    commands: &CommandRegistry,
+    context: &CommandContext,
+    input: &str,
+) -> bool {
+    let last_text = input.rsplit(PIPE).next().unwrap_or_default().trim_start();
+    Invocation::parse_partial(commands, context, last_text)
+        .is_some_and(|invocation| invocation.command.suggests_characters())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::autocomplete_map::autocomplete_names;
+    use crate::command::ArgumentSuggestion;
+
+    #[derive(Clone)]
+    struct TestCommand {
+        name: &'static str,
+        takes_argument: bool,
+        exe_filter: Option<&'static str>,
+    }
+
+    impl Command for TestCommand {
+        fn name(&self) -> String {
+            self.name.to_string()
+        }
+
+        fn execute(&mut self, _ui: &mut UserInterface) -> Result<CommandOutput, Error> {
+            Ok(CommandOutput::Nothing)
+        }
+
+        fn takes_argument(&self) -> bool {
+            self.takes_argument
+        }
+
+        fn is_available(&self, context: &CommandContext) -> bool {
+            match self.exe_filter {
+                Some(exe) => context.foreground_executable_path() == Some(exe),
+                None => true,
+            }
+        }
+
+        fn preview(&self, argument: Option<&str>) -> Option<String> {
+            Some(format!("{} {:?}", self.name, argument))
+        }
+
+        fn suggests_characters(&self) -> bool {
+            self.name == "type"
+        }
+
+        fn suggest_arguments(
+            &self,
+            partial_argument: &str,
+            max_results: usize,
+        ) -> Vec<ArgumentSuggestion> {
+            if self.name != "type" {
+                return vec![];
+            }
+            if partial_argument.is_empty() {
+                return vec![ArgumentSuggestion {
+                    argument: String::from("snowman"),
+                    matches: vec![],
+                    label: None,
+                }];
+            }
+            autocomplete_names(
+                partial_argument,
+                ["snowflake", "snowman"].iter(),
+                max_results,
+            )
+            .into_iter()
+            .map(|(name, matches)| ArgumentSuggestion {
+                argument: name.to_string(),
+                matches,
+                label: Some(name.to_uppercase()),
+            })
+            .collect()
+        }
+    }
+
+    fn make_commands() -> CommandRegistry {
+        let mut commands = CommandRegistry::new();
+        for (name, takes_argument, exe_filter) in [
+            ("insert", true, None),
+            ("insert snowman", false, None),
+            ("insert digraph", true, None),
+            ("copy", true, None),
+            ("quit", false, None),
+            ("type", true, None),
+            ("emacs quit", false, Some("emacs.exe")),
+        ] {
+            commands.add(
+                None,
+                Box::new(TestCommand {
+                    name,
+                    takes_argument,
+                    exe_filter,
+                }),
+            );
+        }
+        commands
+    }
+
+    fn describe(invocation: &Invocation) -> (String, Option<String>) {
+        (invocation.command.name(), invocation.argument.clone())
+    }
+
+    type DescribedSuggestion = (String, Vec<Range<usize>>, Vec<(String, Option<String>)>);
+
+    fn describe_suggestions(
+        suggs: Vec<AutocompleteSuggestion<Pipeline>>,
+    ) -> Vec<DescribedSuggestion> {
+        suggs
+            .into_iter()
+            .map(|sugg| {
+                let stages = sugg.value.stages.iter().map(describe).collect();
+                (sugg.name, sugg.matches, stages)
+            })
+            .collect()
+    }
+
+    fn stage(name: &str, argument: Option<&str>) -> (String, Option<String>) {
+        (name.to_string(), argument.map(String::from))
+    }
+
+    #[test]
+    fn test_parse_finds_exact_matches() {
+        let commands = make_commands();
+        let invocation =
+            Invocation::parse(&commands, &CommandContext::default(), " insert snowman ").unwrap();
+        assert_eq!(describe(&invocation), stage("insert snowman", None));
+    }
+
+    #[test]
+    fn test_parse_finds_arguments() {
+        let commands = make_commands();
+        let invocation =
+            Invocation::parse(&commands, &CommandContext::default(), "copy hi  there").unwrap();
+        assert_eq!(describe(&invocation), stage("copy", Some("hi  there")));
+    }
+
+    #[test]
+    fn test_parse_prefers_longest_command_name() {
+        let commands = make_commands();
+        let invocation =
+            Invocation::parse(&commands, &CommandContext::default(), "insert digraph e'").unwrap();
+        assert_eq!(describe(&invocation), stage("insert digraph", Some("e'")));
+    }
+
+    #[test]
+    fn test_parse_ignores_case_of_names_but_not_arguments() {
+        let commands = make_commands();
+        let invocation =
+            Invocation::parse(&commands, &CommandContext::default(), "Insert Digraph A:").unwrap();
+        assert_eq!(describe(&invocation), stage("insert digraph", Some("A:")));
+    }
+
+    #[test]
+    fn test_parse_finds_qualified_names() {
+        let mut commands = make_commands();
+        commands.add(
+            Some(String::from("hotkeys")),
+            Box::new(TestCommand {
+                name: "copy",
+                takes_argument: true,
+                exe_filter: None,
+            }),
+        );
+        let invocation =
+            Invocation::parse(&commands, &CommandContext::default(), "hotkeys: copy hi").unwrap();
+        assert_eq!(describe(&invocation), stage("copy", Some("hi")));
+        assert_eq!(invocation.plugin.as_deref(), Some("hotkeys"));
+    }
+
+    #[test]
+    fn test_parse_ignores_arguments_to_commands_that_take_none() {
+        let commands = make_commands();
+        assert!(Invocation::parse(&commands, &CommandContext::default(), "quit now").is_none());
+    }
+
+    #[test]
+    fn test_autocomplete_works_without_pipes() {
+        let commands = make_commands();
+        assert_eq!(
+            describe_suggestions(autocomplete(&commands, &CommandContext::default(), "qu", 5)),
+            vec![("quit".to_string(), vec![0..2], vec![stage("quit", None)])]
+        );
+    }
+
+    #[test]
+    fn test_autocomplete_includes_argument_invocations_last() {
+        let commands = make_commands();
+        assert_eq!(
+            describe_suggestions(autocomplete(
+                &commands,
+                &CommandContext::default(),
+                "insert snow",
+                5
+            )),
+            vec![
+                (
+                    "insert snowman".to_string(),
+                    vec![0..11],
+                    vec![stage("insert snowman", None)]
+                ),
+                (
+                    "insert snow".to_string(),
+                    vec![0..11],
+                    vec![stage("insert", Some("snow"))]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_autocomplete_suggests_arguments_after_a_space() {
+        let commands = make_commands();
+        assert_eq!(
+            describe_suggestions(autocomplete(
+                &commands,
+                &CommandContext::default(),
+                "type ",
+                5
+            )),
+            vec![(
+                "type snowman".to_string(),
+                vec![0..5],
+                vec![stage("type", Some("snowman"))]
+            )]
+        );
+        assert_eq!(
+            describe_suggestions(autocomplete(
+                &commands,
+                &CommandContext::default(),
+                "type",
+                5
+            )),
+            vec![("type".to_string(), vec![0..4], vec![stage("type", None)])]
+        );
+    }
+
+    #[test]
+    fn test_suggests_characters_works() {
+        let commands = make_commands();
+        let context = CommandContext::default();
+        assert!(suggests_characters(&commands, &context, "type sn"));
+        assert!(suggests_characters(&commands, &context, "type "));
+        assert!(suggests_characters(&commands, &context, "copy hi | type s"));
+        assert!(!suggests_characters(&commands, &context, "type"));
+        assert!(!suggests_characters(&commands, &context, "copy hi"));
+        assert!(!suggests_characters(&commands, &context, "type hi | copy"));
+    }
+
+    #[test]
+    fn test_autocomplete_arguments_leaves_out_names() {
+        let mut commands = make_commands();
+        commands.add(
+            None,
+            Box::new(TestCommand {
+                name: "type slowly",
+                takes_argument: false,
+                exe_filter: None,
+            }),
+        );
+        let context = CommandContext::default();
+        let names = |suggs: Vec<AutocompleteSuggestion<Pipeline>>| -> Vec<String> {
+            suggs.into_iter().map(|sugg| sugg.name).collect()
+        };
+        assert_eq!(
+            names(autocomplete(&commands, &context, "type s", 5)),
+            vec!["type slowly", "type snowflake", "type snowman", "type s"]
+        );
+        assert_eq!(
+            names(autocomplete_arguments(&commands, &context, "type s", 5)),
+            vec!["type snowflake", "type snowman", "type s"]
+        );
+    }
+
+    #[test]
+    fn test_autocomplete_ignores_unavailable_commands() {
+        let commands = make_commands();
+        let emacs = CommandContext::new(Some("emacs.exe".into()), None, None);
+        assert_eq!(
+            describe_suggestions(autocomplete(&commands, &emacs, "quit", 5)),
+            vec![
+                ("quit".to_string(), vec![0..4], vec![stage("quit", None)]),
+                (
+                    "emacs quit".to_string(),
+                    vec![6..10],
+                    vec![stage("emacs quit", None)]
+                ),
+            ]
+        );
+        assert_eq!(
+            describe_suggestions(autocomplete(
+                &commands,
+                &CommandContext::default(),
+                "quit",
+                5
+            )),
+            vec![("quit".to_string(), vec![0..4], vec![stage("quit", None)])]
+        );
+    }
+
+    #[test]
+    fn test_autocomplete_completes_last_stage() {
+        let commands = make_commands();
+        assert_eq!(
+            describe_suggestions(autocomplete(
+                &commands,
+                &CommandContext::default(),
+                "insert snowman |co",
+                5
+            )),
+            vec![(
+                "insert snowman | copy".to_string(),
+                vec![0..17, 17..19],
+                vec![stage("insert snowman", None), stage("copy", None)]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_autocomplete_suggests_arguments() {
+        let commands = make_commands();
+        let suggs = autocomplete(&commands, &CommandContext::default(), "type  snowm", 5);
+        let labels: Vec<Option<&str>> = suggs.iter().map(|sugg| sugg.value.label()).collect();
+        assert_eq!(labels, vec![Some("SNOWMAN"), None]);
+        assert_eq!(
+            describe_suggestions(suggs),
+            vec![
+                (
+                    "type  snowman".to_string(),
+                    vec![0..6, 6..11],
+                    vec![stage("type", Some("snowman"))]
+                ),
+                (
+                    "type  snowm".to_string(),
+                    vec![0..11],
+                    vec![stage("type", Some("snowm"))]
+                ),
+            ]
+        );
+        let suggs = autocomplete(&commands, &CommandContext::default(), "type snowman", 5);
+        assert_eq!(
+            describe_suggestions(suggs),
+            vec![(
+                "type snowman".to_string(),
+                vec![0..5, 5..12],
+                vec![stage("type", Some("snowman"))]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_preview_only_works_for_single_stages() {
+        let commands = make_commands();
+        let context = CommandContext::default();
+        let previews: Vec<Option<String>> = autocomplete(&commands, &context, "copy hi", 5)
+            .iter()
+            .map(|sugg| sugg.value.preview())
+            .collect();
+        assert_eq!(previews, vec![Some(String::from("copy Some(\"hi\")"))]);
+        let suggs = autocomplete(&commands, &context, "quit | copy", 5);
+        assert_eq!(suggs[0].value.preview(), None);
+    }
+
+    #[test]
+    fn test_autocomplete_requires_earlier_stages_to_be_valid() {
+        let commands = make_commands();
+        assert!(autocomplete(&commands, &CommandContext::default(), "blarg | copy", 5).is_empty());
+    }
+}
@@ -1,13 +1,45 @@
-use crate::ui::UserInterfacePlugin;
+use std::path::Path;
+
+use crate::ui::PluginEntry;
 
 mod builtin;
+mod external;
 mod insert_unicode_characters;
 mod invoke_hotkeys;
+mod scripts;
+mod wasm;
+
+pub fn get_all_plugins() -> Vec<PluginEntry> {
+    let mut plugins = vec![
+        PluginEntry::new("builtin", || Box::new(builtin::BuiltinPlugin::default())),
+        PluginEntry::new("hotkeys", || {
+            Box::new(invoke_hotkeys::InvokeHotkeysPlugin::default())
+        }),
+        PluginEntry::new("unicode", || {
+            Box::new(insert_unicode_characters::InsertUnicodeCharactersPlugin::default())
+        }),
+        PluginEntry::new("scripts", || Box::new(scripts::ScriptsPlugin::default())),
+    ];
+    plugins.extend(external::get_external_plugins());
+    plugins.extend(wasm::get_wasm_plugins());
+    plugins
+}
+
+/// Plugins loaded from files are named after the file, e.g. `emoji.wasm`.
+fn get_plugin_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-pub fn get_all_plugins() -> Vec<Box<dyn UserInterfacePlugin>> {
-    vec![
-        Box::new(builtin::BuiltinPlugin::default()),
-        Box::new(invoke_hotkeys::InvokeHotkeysPlugin::default()),
-        Box::new(insert_unicode_characters::InsertUnicodeCharactersPlugin::default()),
-    ]
+    #[test]
+    fn test_plugins_are_named_after_what_they_build() {
+        for entry in get_all_plugins() {
+            assert_eq!((entry.build)().name(), entry.name);
+        }
+    }
 }
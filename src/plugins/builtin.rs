@@ -1,22 +1,145 @@
+use crate::command::{Command, CommandOutput};
 use crate::error::Error;
-use crate::system::{get_enso_home_dir, open_in_explorer};
+use crate::prompt::Prompt;
+use crate::system::get_enso_home_dir;
 use crate::ui::{UserInterface, UserInterfacePlugin};
 
 #[derive(Default)]
 pub struct BuiltinPlugin;
 
 impl UserInterfacePlugin for BuiltinPlugin {
+    fn name(&self) -> String {
+        String::from("builtin")
+    }
+
+    // Without this plugin, there'd be no way to enable it again.
+    fn can_be_disabled(&self) -> bool {
+        false
+    }
+
     fn init(&mut self, ui: &mut UserInterface) -> Result<(), Error> {
-        ui.add_simple_command("help", |ui| {
-            ui.show_message("Sorry, still need to implement help!")
+        ui.add_simple_command("help", |_ui| {
+            Ok(CommandOutput::ShowMessage(String::from(
+                "Sorry, still need to implement help!",
+            )))
         });
 
-        ui.add_simple_command("quit", |ui| ui.quit());
+        ui.add_simple_command("quit", |_ui| {
+            Ok(CommandOutput::Prompt(Prompt::confirm(
+                "Are you sure you want to quit Enso?",
+                |ui| ui.quit(),
+            )))
+        });
 
         ui.add_simple_command("open enso directory", |_ui| {
-            open_in_explorer(&get_enso_home_dir()?)
+            let path = get_enso_home_dir()?;
+            Ok(CommandOutput::Open(path.to_string_lossy().into_owned()))
+        });
+
+        ui.add_command(Box::new(TextCommand {
+            name: "copy",
+            make_output: CommandOutput::CopyText,
+            describe: |text| format!("Copy \u{201C}{}\u{201D} to the clipboard.", text),
+        }));
+
+        ui.add_command(Box::new(PluginCommand {
+            name: "enable plugin",
+            run: UserInterface::enable_plugin,
+        }));
+        ui.add_command(Box::new(PluginCommand {
+            name: "disable plugin",
+            run: UserInterface::disable_plugin,
+        }));
+
+        ui.add_simple_command("reload plugins", |ui| {
+            ui.reload_plugins()?;
+            Ok(CommandOutput::Nothing)
+        });
+
+        ui.add_simple_command("show plugins", |ui| {
+            let lines: Vec<String> = ui
+                .get_plugin_names()
+                .into_iter()
+                .map(|(name, is_enabled)| {
+                    let status = if is_enabled { "enabled" } else { "disabled" };
+                    format!("{} ({})", name, status)
+                })
+                .collect();
+            Ok(CommandOutput::ShowMessage(lines.join("\n")))
         });
 
         Ok(())
     }
 }
+
+/// A command that does something with the text it's given, e.g. `copy hello`
+/// or `insert snowman | copy`.
+#[derive(Clone)]
+struct TextCommand {
+    name: &'static str,
+    make_output: fn(String) -> CommandOutput,
+    describe: fn(&str) -> String,
+}
+
+impl Command for TextCommand {
+    fn name(&self) -> String {
+        self.name.to_string()
+    }
+
+    fn execute(&mut self, _ui: &mut UserInterface) -> Result<CommandOutput, Error> {
+        Err(Error::new(format!(
+            "Please give me some text, e.g. \u{201C}{} hello\u{201D}.",
+            self.name
+        )))
+    }
+
+    fn takes_argument(&self) -> bool {
+        true
+    }
+
+    fn preview(&self, argument: Option<&str>) -> Option<String> {
+        argument.map(self.describe)
+    }
+
+    fn execute_with_argument(
+        &mut self,
+        _ui: &mut UserInterface,
+        argument: &str,
+    ) -> Result<CommandOutput, Error> {
+        Ok((self.make_output)(argument.to_string()))
+    }
+}
+
+/// A command that does something with the plugin it's given, e.g.
+/// `disable plugin scripts`.
+#[derive(Clone)]
+struct PluginCommand {
+    name: &'static str,
+    run: fn(&mut UserInterface, &str) -> Result<(), Error>,
+}
+
+impl Command for PluginCommand {
+    fn name(&self) -> String {
+        self.name.to_string()
+    }
+
+    fn execute(&mut self, _ui: &mut UserInterface) -> Result<CommandOutput, Error> {
+        Err(Error::new(format!(
+            "Please tell me which plugin, e.g. \u{201C}{} scripts\u{201D}.",
+            self.name
+        )))
+    }
+
+    fn takes_argument(&self) -> bool {
+        true
+    }
+
+    fn execute_with_argument(
+        &mut self,
+        ui: &mut UserInterface,
+        argument: &str,
+    ) -> Result<CommandOutput, Error> {
+        (self.run)(ui, argument.trim())?;
+        Ok(CommandOutput::Nothing)
+    }
+}
@@ -0,0 +1,5 @@
+mod connection;
+mod plugin;
+mod protocol;
+
+pub use plugin::get_external_plugins;
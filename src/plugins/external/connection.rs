@@ -0,0 +1,409 @@
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use super::protocol::{self, CommandInfo, Message, PROTOCOL_VERSION};
+use crate::error::Error;
+use crate::system::type_char;
+
+/// How many times in a row we'll restart a plugin that crashes or stops
+/// responding before giving up on it. Responding to a request resets this, so
+/// that occasional hiccups over a long session don't add up.
+const MAX_RESTARTS: u32 = 3;
+
+const INITIALIZE_TIMEOUT_MS: u64 = 5000;
+
+/// How long a plugin has to exit on its own after being told we're shutting down.
+const SHUTDOWN_TIMEOUT_MS: u64 = 1000;
+
+/// How to launch a plugin, based on its file extension.
+fn get_launcher(path: &Path) -> Option<Vec<String>> {
+    let program = path.to_string_lossy().into_owned();
+    match path.extension()?.to_str()? {
+        "exe" | "bat" | "cmd" => Some(vec![program]),
+        "py" => Some(vec![String::from("python"), program]),
+        "js" => Some(vec![String::from("node"), program]),
+        _ => None,
+    }
+}
+
+/// Shows a message the plugin asked us to show while we were waiting for it,
+/// if messages can be shown at the time.
+pub type MessageSink<'a> = Option<&'a mut dyn FnMut(String) -> Result<(), Error>>;
+
+/// Returns whether the given file looks like something we know how to launch.
+pub fn is_launchable(path: &Path) -> bool {
+    get_launcher(path).is_some()
+}
+
+struct Process {
+    child: Child,
+    stdin: ChildStdin,
+    /// Lines read from the plugin's stdout, or `None` once it's closed.
+    receiver: Receiver<Option<String>>,
+}
+
+impl Process {
+    fn spawn(path: &Path) -> Result<Self, Error> {
+        let launcher =
+            get_launcher(path).ok_or_else(|| Error::new("Don't know how to launch plugin"))?;
+        let mut command = Command::new(&launcher[0]);
+        command
+            .args(&launcher[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit());
+        #[cfg(windows)]
+        {
+            // Don't pop up a console window for every plugin.
+            use std::os::windows::process::CommandExt;
+            const CREATE_NO_WINDOW: u32 = 0x08000000;
+            command.creation_flags(CREATE_NO_WINDOW);
+        }
+        let mut child = command.spawn()?;
+        let stdin = child.stdin.take().expect("stdin should be piped");
+        let stdout = child.stdout.take().expect("stdout should be piped");
+        let (sender, receiver) = channel();
+        thread::Builder::new()
+            .name(format!("Plugin reader: {}", path.display()))
+            .spawn(move || {
+                for line in BufReader::new(stdout).lines() {
+                    let Ok(line) = line else {
+                        break;
+                    };
+                    if sender.send(Some(line)).is_err() {
+                        return;
+                    }
+                }
+                let _ = sender.send(None);
+            })?;
+        Ok(Process {
+            child,
+            stdin,
+            receiver,
+        })
+    }
+
+    fn send(&mut self, line: String) -> Result<(), Error> {
+        writeln!(self.stdin, "{}", line)?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    fn kill(mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+
+    /// Gives the process a chance to exit on its own before killing it.
+    fn wait_or_kill(mut self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        self.kill();
+    }
+}
+
+/// A connection to an external plugin process, which is started lazily and
+/// restarted if it crashes or stops responding.
+pub struct Connection {
+    path: PathBuf,
+    name: String,
+    process: Option<Process>,
+    has_started: bool,
+    next_id: u64,
+    restarts: u32,
+    /// Whether the plugin exited without being asked to since this was last
+    /// checked.
+    crashed: bool,
+    commands: Vec<CommandInfo>,
+    commands_changed: bool,
+}
+
+impl Connection {
+    pub fn new(path: PathBuf) -> Self {
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Connection {
+            path,
+            name,
+            process: None,
+            has_started: false,
+            next_id: 1,
+            restarts: 0,
+            crashed: false,
+            commands: vec![],
+            commands_changed: false,
+        }
+    }
+
+    /// Returns the plugin's current commands if they've changed since the
+    /// last time this was called.
+    pub fn take_changed_commands(&mut self) -> Option<Vec<CommandInfo>> {
+        if self.commands_changed {
+            self.commands_changed = false;
+            Some(self.commands.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether the plugin's process is running, cleaning up after it
+    /// if it's exited.
+    pub fn is_running(&mut self) -> bool {
+        let Some(process) = &mut self.process else {
+            return false;
+        };
+        match process.child.try_wait() {
+            Ok(None) => true,
+            _ => {
+                self.stop();
+                self.crashed = true;
+                false
+            }
+        }
+    }
+
+    /// Returns whether the plugin has crashed since the last time this was
+    /// called, so that the user can be told about it.
+    pub fn take_crash(&mut self) -> bool {
+        self.is_running();
+        std::mem::take(&mut self.crashed)
+    }
+
+    /// Starts the plugin if it isn't already running.
+    pub fn ensure_started(&mut self) -> Result<(), Error> {
+        if self.is_running() {
+            return Ok(());
+        }
+        if self.has_started {
+            if self.restarts >= MAX_RESTARTS {
+                return Err(Error::new(format!(
+                    "The \u{201C}{}\u{201D} plugin has stopped working too many times.",
+                    self.name
+                )));
+            }
+            self.restarts += 1;
+            println!("Restarting plugin \"{}\".", self.name);
+        } else {
+            println!("Starting plugin \"{}\".", self.path.display());
+        }
+        self.process = Some(Process::spawn(&self.path)?);
+        self.has_started = true;
+        // A plugin that didn't finish starting isn't usable, so it's stopped
+        // rather than left for the next request to talk to.
+        let result = self.initialize();
+        if result.is_err() {
+            self.stop();
+        }
+        result
+    }
+
+    fn initialize(&mut self) -> Result<(), Error> {
+        let result = self.request_started(
+            "initialize",
+            json!({ "protocol_version": PROTOCOL_VERSION }),
+            Duration::from_millis(INITIALIZE_TIMEOUT_MS),
+            None,
+        )?;
+        let version = result.get("protocol_version").and_then(Value::as_u64);
+        if version != Some(PROTOCOL_VERSION) {
+            return Err(Error::new(format!(
+                "The \u{201C}{}\u{201D} plugin speaks protocol version {:?}, but Enso speaks {}.",
+                self.name, version, PROTOCOL_VERSION
+            )));
+        }
+        if let Some(name) = result.get("name").and_then(Value::as_str) {
+            self.name = name.to_string();
+        }
+        self.set_commands(&result)?;
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        if let Some(process) = self.process.take() {
+            process.kill();
+        }
+    }
+
+    fn set_commands(&mut self, value: &Value) -> Result<(), Error> {
+        self.commands = protocol::parse_commands(value).map_err(|err| self.error(err))?;
+        self.commands_changed = true;
+        Ok(())
+    }
+
+    fn error<S: AsRef<str>>(&self, message: S) -> Error {
+        Error::new(format!(
+            "The \u{201C}{}\u{201D} plugin had a problem: {}",
+            self.name,
+            message.as_ref()
+        ))
+    }
+
+    fn send(&mut self, line: String) -> Result<(), Error> {
+        let Some(process) = &mut self.process else {
+            return Err(self.error("It isn't running."));
+        };
+        if let Err(err) = process.send(line) {
+            self.stop();
+            return Err(self.error(err.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Sends a request to the plugin and waits for its response, starting the
+    /// plugin first if needed. If the plugin asks us to show messages in the
+    /// meantime, they go to the given sink.
+    pub fn request(
+        &mut self,
+        method: &str,
+        params: Value,
+        timeout: Duration,
+        show_message: MessageSink,
+    ) -> Result<Value, Error> {
+        self.ensure_started()?;
+        self.request_started(method, params, timeout, show_message)
+    }
+
+    fn request_started(
+        &mut self,
+        method: &str,
+        params: Value,
+        timeout: Duration,
+        mut show_message: MessageSink,
+    ) -> Result<Value, Error> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(protocol::request(id, method, params))?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let Some(message) = self.receive(remaining)? else {
+                self.stop();
+                return Err(self.error(format!(
+                    "It didn't respond to \u{201C}{}\u{201D} in time.",
+                    method
+                )));
+            };
+            match message {
+                Message::Response {
+                    id: response_id,
+                    result,
+                } if response_id == id => {
+                    self.restarts = 0;
+                    return result.map_err(|err| self.error(err));
+                }
+                Message::Response { .. } => {}
+                Message::Request { id, method, params } => {
+                    let show_message = show_message.as_mut().map(|show| &mut **show as _);
+                    self.handle_request(id, &method, &params, show_message)?;
+                }
+            }
+        }
+    }
+
+    /// Waits for the next message from the plugin, returning `None` if none
+    /// arrives in time.
+    fn receive(&mut self, timeout: Duration) -> Result<Option<Message>, Error> {
+        loop {
+            let Some(process) = &mut self.process else {
+                return Err(self.error("It isn't running."));
+            };
+            let line = match process.receiver.recv_timeout(timeout) {
+                Ok(Some(line)) => line,
+                Err(RecvTimeoutError::Timeout) => return Ok(None),
+                Ok(None) | Err(RecvTimeoutError::Disconnected) => {
+                    self.stop();
+                    self.crashed = true;
+                    return Err(self.error("It exited unexpectedly."));
+                }
+            };
+            match protocol::parse_message(&line) {
+                Ok(message) => return Ok(Some(message)),
+                Err(err) => eprintln!(
+                    "Ignoring bad message from plugin \"{}\": {}",
+                    self.name, err
+                ),
+            }
+        }
+    }
+
+    fn handle_request(
+        &mut self,
+        id: Option<u64>,
+        method: &str,
+        params: &Value,
+        show_message: MessageSink,
+    ) -> Result<(), Error> {
+        let result = match method {
+            "show_message" => match (protocol::parse_text_param(params), show_message) {
+                (Ok(text), Some(show)) => show(text).map_err(|err| err.to_string()),
+                (Ok(_), None) => Err(String::from("Messages can't be shown right now.")),
+                (Err(err), _) => Err(err),
+            },
+            "type_char" => protocol::parse_text_param(params)
+                .and_then(|text| type_char(&text).map_err(|err| err.to_string())),
+            "register_commands" => self.set_commands(params).map_err(|err| err.to_string()),
+            _ => Err(format!("Unknown method \"{}\"", method)),
+        };
+        let result = result.map(|_| Value::Null);
+        match id {
+            Some(id) => self.send(protocol::response(id, result)),
+            None => {
+                if let Err(err) = result {
+                    eprintln!(
+                        "Error handling \"{}\" from plugin \"{}\": {}",
+                        method, self.name, err
+                    );
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Sends a notification to the plugin, if it's running, after handling
+    /// anything it's sent us since we last heard from it.
+    pub fn notify(
+        &mut self,
+        method: &str,
+        params: Value,
+        show_message: &mut dyn FnMut(String) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        if self.process.is_none() {
+            return Ok(());
+        }
+        while let Some(message) = self.receive(Duration::from_millis(0))? {
+            if let Message::Request { id, method, params } = message {
+                self.handle_request(id, &method, &params, Some(&mut *show_message))?;
+            }
+        }
+        self.send(protocol::notification(method, params))
+    }
+
+    /// Tells the plugin we're shutting down and stops it.
+    pub fn shutdown(&mut self) {
+        if self.process.is_some() {
+            let _ = self.send(protocol::notification("shutdown", Value::Null));
+        }
+        if let Some(process) = self.process.take() {
+            process.wait_or_kill(Duration::from_millis(SHUTDOWN_TIMEOUT_MS));
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use super::connection::{is_launchable, Connection};
+use super::protocol::{self, CommandInfo};
+use crate::command::{Command, CommandContext, CommandOutput};
+use crate::error::Error;
+use crate::plugins::get_plugin_name;
+use crate::system::get_enso_home_dir;
+use crate::task::Notifier;
+use crate::ui::{PluginEntry, UserInterface, UserInterfacePlugin};
+
+const EXECUTE_TIMEOUT_MS: u64 = 10000;
+
+// Previews are requested as the user types, so they need to be snappy.
+const PREVIEW_TIMEOUT_MS: u64 = 500;
+
+/// The connection is shared with the worker threads that run the plugin's
+/// commands and fetch its previews.
+type SharedConnection = Arc<Mutex<Connection>>;
+
+/// The most recent preview of each command, along with the argument it was
+/// fetched for.
+type PreviewCache = Arc<Mutex<HashMap<String, (Option<String>, Option<String>)>>>;
+
+fn lock(connection: &SharedConnection) -> MutexGuard<'_, Connection> {
+    // A panic while talking to the plugin doesn't leave the connection in a
+    // state we can't recover from, since it restarts broken plugins anyway.
+    connection.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Like `lock`, but returns `None` rather than waiting if another thread is
+/// using the connection.
+fn try_lock(connection: &SharedConnection) -> Option<MutexGuard<'_, Connection>> {
+    match connection.try_lock() {
+        Ok(connection) => Some(connection),
+        Err(TryLockError::Poisoned(err)) => Some(err.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+/// Returns a plugin for every launchable file in `~/.enso/plugins`.
+pub fn get_external_plugins() -> Vec<PluginEntry> {
+    let Ok(mut plugins_dir) = get_enso_home_dir() else {
+        return vec![];
+    };
+    plugins_dir.push("plugins");
+    let Ok(entries) = std::fs::read_dir(&plugins_dir) else {
+        return vec![];
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && is_launchable(path))
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            PluginEntry::new(get_plugin_name(&path), move || {
+                Box::new(ExternalPlugin::new(path.clone()))
+            })
+        })
+        .collect()
+}
+
+/// A plugin that runs as a separate process, talking to Enso over the
+/// protocol described in the `protocol` module.
+pub struct ExternalPlugin {
+    name: String,
+    connection: SharedConnection,
+    previewer: Option<Previewer>,
+    commands_loaded: Vec<String>,
+}
+
+impl ExternalPlugin {
+    pub fn new(path: PathBuf) -> Self {
+        ExternalPlugin {
+            name: get_plugin_name(&path),
+            connection: Arc::new(Mutex::new(Connection::new(path))),
+            previewer: None,
+            commands_loaded: vec![],
+        }
+    }
+
+    /// Makes the UI's commands match the ones the plugin most recently told
+    /// us about.
+    fn sync_commands(&mut self, ui: &mut UserInterface) {
+        let Some(infos) = lock(&self.connection).take_changed_commands() else {
+            return;
+        };
+        for name in self.commands_loaded.drain(..) {
+            ui.remove_command(name);
+        }
+        for info in infos {
+            if ui.has_command(&info.name) {
+                println!("Command \"{}\" already exists, skipping.", info.name);
+                continue;
+            }
+            self.commands_loaded.push(info.name.clone());
+            ui.add_command(Box::new(ExternalCommand {
+                info,
+                connection: self.connection.clone(),
+                previewer: self.previewer.clone(),
+            }));
+        }
+    }
+
+    fn notify(&mut self, ui: &mut UserInterface, method: &str, params: Value) -> Result<(), Error> {
+        // If the connection is busy, one of the plugin's commands or previews
+        // is running, and the plugin can't be told about anything until it's
+        // done.
+        let Some(mut connection) = try_lock(&self.connection) else {
+            return Ok(());
+        };
+        if connection.take_crash() {
+            let restart = if connection.is_running() {
+                ""
+            } else {
+                " It'll be restarted the next time it's needed."
+            };
+            ui.show_message(format!(
+                "The \u{201C}{}\u{201D} plugin stopped unexpectedly.{}",
+                self.name, restart
+            ))?;
+        }
+        let result = connection.notify(method, params, &mut |text| ui.show_message(text));
+        drop(connection);
+        self.sync_commands(ui);
+        result
+    }
+}
+
+impl UserInterfacePlugin for ExternalPlugin {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn init(&mut self, ui: &mut UserInterface) -> Result<(), Error> {
+        let notify = ui.preview_change_notifier();
+        self.previewer = Some(Previewer::spawn(
+            &self.name,
+            self.connection.clone(),
+            notify,
+        )?);
+        // A broken plugin shouldn't stop Enso from starting.
+        if let Err(error) = lock(&self.connection).ensure_started() {
+            ui.show_message(format!(
+                "An error occurred when starting a plugin:\n{}",
+                error
+            ))?;
+        }
+        self.sync_commands(ui);
+        Ok(())
+    }
+
+    fn on_quasimode_start(&mut self, ui: &mut UserInterface) -> Result<(), Error> {
+        self.notify(ui, "quasimode_start", Value::Null)
+    }
+
+    fn on_input_changed(&mut self, ui: &mut UserInterface, input: &str) -> Result<(), Error> {
+        self.notify(ui, "input_changed", json!({ "input": input }))
+    }
+
+    fn on_quasimode_end(&mut self, ui: &mut UserInterface) -> Result<(), Error> {
+        self.notify(ui, "quasimode_end", Value::Null)
+    }
+
+    fn on_command_executed(
+        &mut self,
+        ui: &mut UserInterface,
+        name: &str,
+        _output: &CommandOutput,
+    ) -> Result<(), Error> {
+        self.notify(ui, "command_executed", json!({ "command": name }))
+    }
+
+    fn on_command_failed(
+        &mut self,
+        ui: &mut UserInterface,
+        name: &str,
+        error: &Error,
+    ) -> Result<(), Error> {
+        let params = json!({ "command": name, "error": error.to_string() });
+        self.notify(ui, "command_failed", params)
+    }
+
+    fn on_message_dismissed(&mut self, ui: &mut UserInterface) -> Result<(), Error> {
+        self.notify(ui, "message_dismissed", Value::Null)
+    }
+
+    fn on_shutdown(&mut self, _ui: &mut UserInterface) -> Result<(), Error> {
+        if let Some(mut connection) = try_lock(&self.connection) {
+            connection.shutdown();
+        }
+        Ok(())
+    }
+}
+
+/// Fetches previews from the plugin on a worker thread, so that a slow plugin
+/// doesn't hold up typing. Until a command's preview arrives, the last one
+/// fetched for it is shown instead.
+#[derive(Clone)]
+struct Previewer {
+    requests: Sender<(String, Option<String>)>,
+    cache: PreviewCache,
+}
+
+impl Previewer {
+    fn spawn(name: &str, connection: SharedConnection, notify: Notifier) -> Result<Self, Error> {
+        let (requests, receiver) = channel::<(String, Option<String>)>();
+        let cache = PreviewCache::default();
+        let thread_cache = cache.clone();
+        // The thread exits once the plugin and its commands are gone, since
+        // they hold the only senders.
+        thread::Builder::new()
+            .name(format!("Previews: {}", name))
+            .spawn(move || {
+                while let Ok(mut request) = receiver.recv() {
+                    // The user has already moved on from all but the most
+                    // recent request.
+                    while let Ok(newer) = receiver.try_recv() {
+                        request = newer;
+                    }
+                    let (command, argument) = request;
+                    let result = {
+                        let mut connection = lock(&connection);
+                        // Previews are requested as the user types, which
+                        // isn't the time to start a plugin.
+                        if !connection.is_running() {
+                            continue;
+                        }
+                        connection.request(
+                            "preview",
+                            json!({ "command": command, "argument": argument }),
+                            Duration::from_millis(PREVIEW_TIMEOUT_MS),
+                            None,
+                        )
+                    };
+                    let preview = match result {
+                        Ok(Value::String(preview)) => Some(preview),
+                        Ok(_) => None,
+                        Err(error) => {
+                            eprintln!("{}", error);
+                            None
+                        }
+                    };
+                    thread_cache
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .insert(command, (argument, preview));
+                    notify();
+                }
+            })?;
+        Ok(Previewer { requests, cache })
+    }
+
+    /// Returns the last preview fetched for the command, asking for a fresh
+    /// one if it was for a different argument.
+    fn get(&self, command: &str, argument: Option<&str>) -> Option<String> {
+        let cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        let cached = cache.get(command);
+        if cached.map(|(cached_argument, _)| cached_argument.as_deref()) != Some(argument) {
+            // If the thread is gone, so is the plugin, and there's nobody to
+            // ask.
+            let _ = self
+                .requests
+                .send((command.to_string(), argument.map(String::from)));
+        }
+        cached.and_then(|(_, preview)| preview.clone())
+    }
+}
+
+#[derive(Clone)]
+struct ExternalCommand {
+    info: CommandInfo,
+    connection: SharedConnection,
+    previewer: Option<Previewer>,
+}
+
+impl ExternalCommand {
+    /// Runs the command as a task, since plugins can take a while to respond.
+    /// Messages the plugin shows in the meantime become the task's status.
+    fn run(&self, ui: &mut UserInterface, argument: Option<&str>) -> Result<CommandOutput, Error> {
+        let connection = self.connection.clone();
+        let params = json!({ "command": self.info.name, "argument": argument });
+        ui.spawn_task(&self.info.name, move |ctx| {
+            let mut connection = lock(&connection);
+            let result = connection.request(
+                "execute",
+                params,
+                Duration::from_millis(EXECUTE_TIMEOUT_MS),
+                Some(&mut |text| {
+                    ctx.set_status(text);
+                    Ok(())
+                }),
+            );
+            if result.is_err() {
+                // The error already says what happened, so the crash doesn't
+                // need to be reported again.
+                connection.take_crash();
+            }
+            protocol::parse_output(&result?).map_err(Error::new)
+        })?;
+        Ok(CommandOutput::Nothing)
+    }
+}
+
+impl Command for ExternalCommand {
+    fn name(&self) -> String {
+        self.info.name.clone()
+    }
+
+    fn execute(&mut self, ui: &mut UserInterface) -> Result<CommandOutput, Error> {
+        self.run(ui, None)
+    }
+
+    fn takes_argument(&self) -> bool {
+        self.info.takes_argument
+    }
+
+    fn execute_with_argument(
+        &mut self,
+        ui: &mut UserInterface,
+        argument: &str,
+    ) -> Result<CommandOutput, Error> {
+        self.run(ui, Some(argument))
+    }
+
+    fn is_available(&self, context: &CommandContext) -> bool {
+        match (&self.info.exe_filter, context.foreground_executable_path()) {
+            (Some(exe_filter), Some(exe_path)) => exe_path.contains(exe_filter.as_str()),
+            _ => true,
+        }
+    }
+
+    fn preview(&self, argument: Option<&str>) -> Option<String> {
+        if !self.info.has_preview {
+            return None;
+        }
+        self.previewer.as_ref()?.get(&self.info.name, argument)
+    }
+}
@@ -0,0 +1,285 @@
+//! The protocol Enso uses to talk to external plugins.
+//!
+//! An external plugin is a child process that exchanges [JSON-RPC 2.0][]
+//! messages with Enso over its stdin and stdout, one message per line.
+//! Anything the plugin writes to stderr is passed through to Enso's console.
+//!
+//! Enso sends the following requests, which the plugin must respond to:
+//!
+//! * `initialize` with `{"protocol_version": 1}`. The result must be
+//!   `{"protocol_version": 1, "name": "...", "commands": [...]}`, where each
+//!   command is `{"name": "...", "takes_argument": false, "exe_filter": null,
+//!   "has_preview": false}`. Only `name` is required.
+//! * `execute` with `{"command": "...", "argument": null}`. The result is the
+//!   command's output (see below).
+//! * `preview` with `{"command": "...", "argument": null}`, for commands that
+//!   said they have previews. The result is a string, or `null`. Previews are
+//!   requested in the background, and until one arrives, the command's
+//!   previous preview is shown.
+//!
+//! Enso also sends the following notifications, which need no response, but
+//! not while it's waiting for a response to one of its requests:
+//! `quasimode_start`, `quasimode_end`, `input_changed` (`{"input": "..."}`),
+//! `command_executed` (`{"command": "..."}`), `command_failed`
+//! (`{"command": "...", "error": "..."}`), `message_dismissed` and `shutdown`.
+//!
+//! A command's output is one of `null`, `{"type": "insert_text", "text":
+//! "..."}`, `{"type": "show_message", "text": "..."}`, `{"type": "copy_text",
+//! "text": "..."}` or `{"type": "open", "target": "..."}`.
+//!
+//! While Enso is waiting for a response, the plugin can send it these
+//! requests (or notifications, if it doesn't care about the result):
+//!
+//! * `show_message` with `{"text": "..."}`. During `execute`, the text is
+//!   shown as the command's status.
+//! * `type_char` with `{"text": "..."}`.
+//! * `register_commands` with `{"commands": [...]}`, which replaces the
+//!   plugin's commands with the given ones.
+//!
+//! Messages the plugin sends at other times are handled the next time Enso
+//! notifies it of something.
+//!
+//! [JSON-RPC 2.0]: https://www.jsonrpc.org/specification
+
+use serde_json::{json, Map, Value};
+
+use crate::command::CommandOutput;
+
+/// This should be incremented whenever the protocol changes in a way that
+/// isn't backwards-compatible.
+pub const PROTOCOL_VERSION: u64 = 1;
+
+/// A message sent to Enso by a plugin.
+#[derive(Debug, PartialEq)]
+pub enum Message {
+    /// A response to one of Enso's requests.
+    Response {
+        id: u64,
+        result: Result<Value, String>,
+    },
+    /// A request from the plugin. If it has no id, it's a notification and
+    /// shouldn't be responded to.
+    Request {
+        id: Option<u64>,
+        method: String,
+        params: Value,
+    },
+}
+
+/// What a plugin told us about one of its commands.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandInfo {
+    pub name: String,
+    pub takes_argument: bool,
+    pub exe_filter: Option<String>,
+    pub has_preview: bool,
+}
+
+pub fn request(id: u64, method: &str, params: Value) -> String {
+    json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}).to_string()
+}
+
+pub fn notification(method: &str, params: Value) -> String {
+    json!({"jsonrpc": "2.0", "method": method, "params": params}).to_string()
+}
+
+pub fn response(id: u64, result: Result<Value, String>) -> String {
+    match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(message) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {"code": -32000, "message": message}
+        }),
+    }
+    .to_string()
+}
+
+pub fn parse_message(line: &str) -> Result<Message, String> {
+    let value: Value = serde_json::from_str(line).map_err(|err| err.to_string())?;
+    let object = value
+        .as_object()
+        .ok_or_else(|| String::from("Message is not an object"))?;
+    let id = object.get("id").and_then(Value::as_u64);
+    if let Some(method) = object.get("method").and_then(Value::as_str) {
+        return Ok(Message::Request {
+            id,
+            method: method.to_string(),
+            params: object.get("params").cloned().unwrap_or(Value::Null),
+        });
+    }
+    let id = id.ok_or_else(|| String::from("Response has no id"))?;
+    let result = match object.get("error") {
+        Some(error) => Err(error
+            .get("message")
+            .and_then(Value::as_str)
+            .unwrap_or("Unknown error")
+            .to_string()),
+        None => Ok(object.get("result").cloned().unwrap_or(Value::Null)),
+    };
+    Ok(Message::Response { id, result })
+}
+
+fn get_str<'a>(object: &'a Map<String, Value>, key: &str) -> Result<&'a str, String> {
+    object
+        .get(key)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("Expected \"{}\" to be a string", key))
+}
+
+/// Returns the `text` parameter of a `show_message` or `type_char` request.
+pub fn parse_text_param(params: &Value) -> Result<String, String> {
+    let object = params
+        .as_object()
+        .ok_or_else(|| String::from("Expected params to be an object"))?;
+    Ok(get_str(object, "text")?.to_string())
+}
+
+pub fn parse_commands(value: &Value) -> Result<Vec<CommandInfo>, String> {
+    let commands = value
+        .get("commands")
+        .and_then(Value::as_array)
+        .ok_or_else(|| String::from("Expected \"commands\" to be an array"))?;
+    let mut result = Vec::with_capacity(commands.len());
+    for command in commands {
+        let object = command
+            .as_object()
+            .ok_or_else(|| String::from("Expected command to be an object"))?;
+        let get_bool = |key| object.get(key).and_then(Value::as_bool).unwrap_or(false);
+        result.push(CommandInfo {
+            name: get_str(object, "name")?.to_string(),
+            takes_argument: get_bool("takes_argument"),
+            exe_filter: object
+                .get("exe_filter")
+                .and_then(Value::as_str)
+                .map(String::from),
+            has_preview: get_bool("has_preview"),
+        });
+    }
+    Ok(result)
+}
+
+pub fn parse_output(value: &Value) -> Result<CommandOutput, String> {
+    let object = match value {
+        Value::Null => return Ok(CommandOutput::Nothing),
+        Value::Object(object) => object,
+        _ => return Err(String::from("Expected output to be null or an object")),
+    };
+    let text = || get_str(object, "text").map(String::from);
+    match get_str(object, "type")? {
+        "nothing" => Ok(CommandOutput::Nothing),
+        "insert_text" => Ok(CommandOutput::InsertText(text()?)),
+        "show_message" => Ok(CommandOutput::ShowMessage(text()?)),
+        "copy_text" => Ok(CommandOutput::CopyText(text()?)),
+        "open" => Ok(CommandOutput::Open(get_str(object, "target")?.to_string())),
+        other => Err(format!("Unknown output type \"{}\"", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_message_parses_responses() {
+        assert_eq!(
+            parse_message(r#"{"jsonrpc": "2.0", "id": 5, "result": "hi"}"#),
+            Ok(Message::Response {
+                id: 5,
+                result: Ok(json!("hi"))
+            })
+        );
+        assert_eq!(
+            parse_message(r#"{"id": 5, "error": {"code": 1, "message": "nope"}}"#),
+            Ok(Message::Response {
+                id: 5,
+                result: Err(String::from("nope"))
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_message_parses_requests_and_notifications() {
+        assert_eq!(
+            parse_message(r#"{"id": 1, "method": "show_message", "params": {"text": "hi"}}"#),
+            Ok(Message::Request {
+                id: Some(1),
+                method: String::from("show_message"),
+                params: json!({"text": "hi"})
+            })
+        );
+        assert_eq!(
+            parse_message(r#"{"method": "type_char"}"#),
+            Ok(Message::Request {
+                id: None,
+                method: String::from("type_char"),
+                params: Value::Null
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_message_rejects_garbage() {
+        assert!(parse_message("blarg").is_err());
+        assert!(parse_message("[]").is_err());
+        assert!(parse_message(r#"{"result": 1}"#).is_err());
+    }
+
+    #[test]
+    fn test_response_works() {
+        assert_eq!(
+            response(1, Ok(Value::Null)),
+            r#"{"id":1,"jsonrpc":"2.0","result":null}"#
+        );
+        let error = parse_message(&response(2, Err(String::from("nope")))).unwrap();
+        assert_eq!(
+            error,
+            Message::Response {
+                id: 2,
+                result: Err(String::from("nope"))
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_commands_works() {
+        let commands = parse_commands(&json!({"commands": [
+            {"name": "boop"},
+            {"name": "say", "takes_argument": true, "exe_filter": "notepad.exe"},
+        ]}))
+        .unwrap();
+        assert_eq!(
+            commands,
+            vec![
+                CommandInfo {
+                    name: String::from("boop"),
+                    takes_argument: false,
+                    exe_filter: None,
+                    has_preview: false,
+                },
+                CommandInfo {
+                    name: String::from("say"),
+                    takes_argument: true,
+                    exe_filter: Some(String::from("notepad.exe")),
+                    has_preview: false,
+                },
+            ]
+        );
+        assert!(parse_commands(&json!({"commands": [{}]})).is_err());
+    }
+
+    #[test]
+    fn test_parse_output_works() {
+        assert_eq!(parse_output(&Value::Null), Ok(CommandOutput::Nothing));
+        assert_eq!(
+            parse_output(&json!({"type": "insert_text", "text": "hi"})),
+            Ok(CommandOutput::InsertText(String::from("hi")))
+        );
+        assert_eq!(
+            parse_output(&json!({"type": "open", "target": "C:\\"})),
+            Ok(CommandOutput::Open(String::from("C:\\")))
+        );
+        assert!(parse_output(&json!({"type": "insert_text"})).is_err());
+        assert!(parse_output(&json!({"type": "blarg"})).is_err());
+    }
+}
@@ -1,4 +1,11 @@
 mod cldr_annotations;
+mod describe;
+mod digraphs;
+mod emoji;
+mod latex;
+mod latex_symbols;
+mod lookup;
 mod plugin;
+mod usage;
 
 pub use plugin::InsertUnicodeCharactersPlugin;
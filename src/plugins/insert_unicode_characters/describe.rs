@@ -0,0 +1,171 @@
+use unicode_general_category::{get_general_category, GeneralCategory};
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::lookup::CharacterNames;
+
+/// The most code points we'll describe at once, so that selecting a whole
+/// paragraph by accident doesn't produce a message that fills the screen.
+const MAX_DESCRIBED_CODE_POINTS: usize = 20;
+
+const DOTTED_CIRCLE: char = '\u{25CC}';
+
+/// Returns a readable name for the given category, e.g. `lowercase letter`.
+fn get_category_name(category: GeneralCategory) -> String {
+    let mut name = String::new();
+    for ch in format!("{:?}", category).chars() {
+        if ch.is_ascii_uppercase() && !name.is_empty() {
+            name.push(' ');
+        }
+        name.push(ch.to_ascii_lowercase());
+    }
+    name
+}
+
+fn is_invisible(ch: char) -> bool {
+    ch.is_whitespace()
+        || matches!(
+            get_general_category(ch),
+            GeneralCategory::Control
+                | GeneralCategory::Format
+                | GeneralCategory::SpaceSeparator
+                | GeneralCategory::LineSeparator
+                | GeneralCategory::ParagraphSeparator
+        )
+}
+
+fn is_combining_mark(ch: char) -> bool {
+    matches!(
+        get_general_category(ch),
+        GeneralCategory::NonspacingMark
+            | GeneralCategory::SpacingMark
+            | GeneralCategory::EnclosingMark
+    )
+}
+
+/// Returns a form of the given grapheme cluster that can be seen, by writing
+/// invisible characters as their code points, e.g. `[U+200B]`, and putting
+/// combining marks that have nothing to combine with on a dotted circle.
+fn get_visible_form(grapheme: &str) -> String {
+    let mut visible = String::new();
+    if grapheme.chars().next().is_some_and(is_combining_mark) {
+        visible.push(DOTTED_CIRCLE);
+    }
+    for ch in grapheme.chars() {
+        if is_invisible(ch) {
+            visible.push_str(&format!("[U+{:04X}]", ch as u32));
+        } else {
+            visible.push(ch);
+        }
+    }
+    visible
+}
+
+/// Returns the official Unicode name of the given character, or a
+/// description of it if it doesn't have one, e.g. `<control>`.
+fn get_official_name(ch: char) -> String {
+    if let Some(name) = unicode_names2::name(ch) {
+        return name.to_string();
+    }
+    match get_general_category(ch) {
+        GeneralCategory::Control => String::from("<control>"),
+        GeneralCategory::PrivateUse => String::from("<private use>"),
+        GeneralCategory::Unassigned => String::from("<unassigned>"),
+        _ => String::from("<unknown>"),
+    }
+}
+
+fn describe_code_point(ch: char) -> String {
+    let category = get_general_category(ch);
+    let mut utf8 = [0; 4];
+    let utf8: Vec<String> = ch
+        .encode_utf8(&mut utf8)
+        .bytes()
+        .map(|byte| format!("{:02X}", byte))
+        .collect();
+    let mut utf16 = [0; 2];
+    let utf16: Vec<String> = ch
+        .encode_utf16(&mut utf16)
+        .iter()
+        .map(|unit| format!("{:04X}", unit))
+        .collect();
+    format!(
+        "U+{:04X} {}: {} ({}), UTF-8 {}, UTF-16 {}",
+        ch as u32,
+        get_official_name(ch),
+        category.abbreviation(),
+        get_category_name(category),
+        utf8.join(" "),
+        utf16.join(" ")
+    )
+}
+
+/// Describes every code point in the given text, grouped by the grapheme
+/// clusters (i.e. user-perceived characters) they make up, e.g. an emoji
+/// made of several code points.
+pub fn describe_text(text: &str, names: &CharacterNames) -> String {
+    let mut lines = vec![];
+    let mut described = 0;
+    for grapheme in text.graphemes(true) {
+        if described >= MAX_DESCRIBED_CODE_POINTS {
+            let remaining = text.chars().count() - described;
+            lines.push(format!("\u{2026}and {} more code points.", remaining));
+            break;
+        }
+        let mut heading = format!("\u{201C}{}\u{201D}", get_visible_form(grapheme));
+        if let Some(name) = names.find_name(grapheme) {
+            heading.push_str(&format!(" {}", name));
+        }
+        lines.push(heading);
+        for ch in grapheme.chars().take(MAX_DESCRIBED_CODE_POINTS - described) {
+            lines.push(format!("  {}", describe_code_point(ch)));
+            described += 1;
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn describe(text: &str) -> String {
+        describe_text(text, &CharacterNames::default())
+    }
+
+    #[test]
+    fn test_describe_text_works() {
+        assert_eq!(
+            describe("\u{2603}"),
+            "\u{201C}\u{2603}\u{201D} snowman\n  \
+             U+2603 SNOWMAN: So (other symbol), UTF-8 E2 98 83, UTF-16 2603"
+        );
+    }
+
+    #[test]
+    fn test_describe_text_groups_code_points_by_grapheme() {
+        assert_eq!(
+            describe("e\u{301}\u{1F600}"),
+            "\u{201C}e\u{301}\u{201D}\n  \
+             U+0065 LATIN SMALL LETTER E: Ll (lowercase letter), UTF-8 65, UTF-16 0065\n  \
+             U+0301 COMBINING ACUTE ACCENT: Mn (nonspacing mark), UTF-8 CC 81, UTF-16 0301\n\
+             \u{201C}\u{1F600}\u{201D} grinning face\n  \
+             U+1F600 GRINNING FACE: So (other symbol), UTF-8 F0 9F 98 80, UTF-16 D83D DE00"
+        );
+    }
+
+    #[test]
+    fn test_describe_text_shows_invisible_characters() {
+        let description = describe(" \u{200B}\u{301}\t");
+        assert!(description.contains("\u{201C}[U+0020]\u{201D}"));
+        assert!(description.contains("\u{201C}[U+200B]\u{201D}"));
+        assert!(description.contains("\u{201C}\u{25CC}\u{301}\u{201D}"));
+        assert!(description.contains("U+0009 <control>: Cc (control)"));
+    }
+
+    #[test]
+    fn test_describe_text_limits_code_points() {
+        let description = describe(&"a".repeat(25));
+        assert_eq!(description.lines().count(), 41);
+        assert!(description.ends_with("\u{2026}and 5 more code points."));
+    }
+}
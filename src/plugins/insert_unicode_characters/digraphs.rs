@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use crate::error::Error;
+use crate::system::get_enso_home_dir;
+
+/// The RFC 1345 digraphs, e.g. `e'` for é.
+const BUILTIN_DIGRAPHS: &str = include_str!("../../../data/digraphs.txt");
+
+/// The user's own digraphs, which override the built-in ones.
+const DIGRAPHS_FILENAME: &str = "digraphs.txt";
+
+/// Two-character mnemonics for characters, e.g. `a:` for ä and `->` for →.
+#[derive(Clone, Debug, Default)]
+pub struct Digraphs {
+    digraphs: HashMap<String, String>,
+}
+
+/// Parses the text a digraph types, which is either a code point like
+/// `U+00E9` or the text itself.
+fn parse_text(value: &str) -> Option<String> {
+    let Some(hex) = value.strip_prefix("U+") else {
+        return Some(value.to_string());
+    };
+    let code_point = u32::from_str_radix(hex, 16).ok()?;
+    char::try_from(code_point).ok().map(String::from)
+}
+
+impl Digraphs {
+    /// Returns just the built-in digraphs.
+    pub fn builtin() -> Self {
+        let mut digraphs = Digraphs::default();
+        digraphs.parse(BUILTIN_DIGRAPHS);
+        digraphs
+    }
+
+    /// Loads the built-in digraphs along with any in `~/.enso/digraphs.txt`,
+    /// returning them along with descriptions of any lines in the latter that
+    /// couldn't be understood.
+    pub fn load() -> Result<(Self, Vec<String>), Error> {
+        let mut digraphs = Digraphs::builtin();
+        let mut path = get_enso_home_dir()?;
+        path.push(DIGRAPHS_FILENAME);
+        let warnings = if path.exists() {
+            println!("Loading digraphs from \"{}\".", path.display());
+            digraphs.parse(&std::fs::read_to_string(&path)?)
+        } else {
+            vec![]
+        };
+        Ok((digraphs, warnings))
+    }
+
+    /// Adds the digraphs in the given text, where each line is a digraph
+    /// followed by what it types, e.g. `e' U+00E9` or `:) ☺`. Anything after
+    /// that is ignored, as are blank lines and lines starting with `#`.
+    fn parse(&mut self, text: &str) -> Vec<String> {
+        let mut warnings = vec![];
+        for (idx, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.split_whitespace();
+            let digraph = parts.next().unwrap_or_default();
+            if digraph.chars().count() != 2 {
+                warnings.push(format!(
+                    "Digraphs should be two characters long, on line {}: {}",
+                    idx + 1,
+                    line
+                ));
+                continue;
+            }
+            let Some(text) = parts.next().and_then(parse_text) else {
+                warnings.push(format!(
+                    "No valid text found for digraph on line {}: {}",
+                    idx + 1,
+                    line
+                ));
+                continue;
+            };
+            self.digraphs.insert(digraph.to_string(), text);
+        }
+        warnings
+    }
+
+    pub fn get(&self, digraph: &str) -> Option<&str> {
+        self.digraphs.get(digraph).map(String::as_str)
+    }
+
+    /// Returns the digraphs starting with the given text, along with what
+    /// they type, sorted by digraph.
+    pub fn find_by_prefix(&self, prefix: &str, max_results: usize) -> Vec<(&str, &str)> {
+        let mut found: Vec<(&str, &str)> = self
+            .digraphs
+            .iter()
+            .filter(|(digraph, _)| digraph.starts_with(prefix))
+            .map(|(digraph, text)| (digraph.as_str(), text.as_str()))
+            .collect();
+        found.sort();
+        found.truncate(max_results);
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_digraphs_work() {
+        assert!(Digraphs::default().parse(BUILTIN_DIGRAPHS).is_empty());
+        let digraphs = Digraphs::builtin();
+        assert_eq!(digraphs.get("e'"), Some("\u{E9}"));
+        assert_eq!(digraphs.get("a:"), Some("\u{E4}"));
+        assert_eq!(digraphs.get("->"), Some("\u{2192}"));
+        assert_eq!(digraphs.get("NS"), Some("\u{A0}"));
+        assert_eq!(digraphs.get("zz"), None);
+    }
+
+    #[test]
+    fn test_parse_overrides_digraphs() {
+        let mut digraphs = Digraphs::builtin();
+        let warnings = digraphs.parse("# Mine\ne' e\n:) \u{263A}\n:( U+D800\nabc U+0041");
+        assert_eq!(digraphs.get("e'"), Some("e"));
+        assert_eq!(digraphs.get(":)"), Some("\u{263A}"));
+        assert_eq!(
+            warnings,
+            vec![
+                String::from("No valid text found for digraph on line 4: :( U+D800"),
+                String::from("Digraphs should be two characters long, on line 5: abc U+0041"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_by_prefix_works() {
+        let digraphs = Digraphs::builtin();
+        let found = digraphs.find_by_prefix("e", 3);
+        assert_eq!(
+            found,
+            vec![("e!", "\u{E8}"), ("e%", "\u{3AD}"), ("e'", "\u{E9}")]
+        );
+    }
+}
@@ -0,0 +1,278 @@
+use emojis::SkinTone;
+
+use crate::error::Error;
+
+/// The skin tones that can be applied to every person in an emoji, along
+/// with what they're called in settings and menus.
+const SKIN_TONES: [(&str, SkinTone); 5] = [
+    ("light", SkinTone::Light),
+    ("medium-light", SkinTone::MediumLight),
+    ("medium", SkinTone::Medium),
+    ("medium-dark", SkinTone::MediumDark),
+    ("dark", SkinTone::Dark),
+];
+
+/// What to do when inserting an emoji that comes in several skin tones.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SkinTonePreference {
+    /// Ask which skin tone to use each time.
+    #[default]
+    Ask,
+    /// Insert the emoji as-is, which is usually yellow.
+    Default,
+    /// Always use the given skin tone.
+    Always(SkinTone),
+}
+
+impl SkinTonePreference {
+    /// Parses a setting like `ask`, `default` or `medium-dark`.
+    pub fn parse(value: &str) -> Result<Self, Error> {
+        match value {
+            "ask" => Ok(SkinTonePreference::Ask),
+            "default" => Ok(SkinTonePreference::Default),
+            _ => SKIN_TONES
+                .iter()
+                .find(|(name, _)| *name == value)
+                .map(|(_, tone)| SkinTonePreference::Always(*tone))
+                .ok_or_else(|| {
+                    let names: Vec<&str> = SKIN_TONES.iter().map(|(name, _)| *name).collect();
+                    Error::new(format!(
+                        "\u{201C}{}\u{201D} isn't a skin tone. Please use ask, default, or one of: {}.",
+                        value,
+                        names.join(", ")
+                    ))
+                }),
+        }
+    }
+}
+
+/// The genders an emoji of a person can be given, along with what they're
+/// called in settings and menus.
+const GENDERS: [(&str, Gender); 2] = [("woman", Gender::Woman), ("man", Gender::Man)];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Gender {
+    Woman,
+    Man,
+}
+
+/// What to do when inserting an emoji of a person that also comes as a woman
+/// and a man, e.g. `person running`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum GenderPreference {
+    /// Ask which gender to use each time.
+    #[default]
+    Ask,
+    /// Insert the emoji as-is, which is usually gender-neutral.
+    Default,
+    /// Always use the given gender.
+    Always(Gender),
+}
+
+impl GenderPreference {
+    /// Parses a setting like `ask`, `default` or `woman`.
+    pub fn parse(value: &str) -> Result<Self, Error> {
+        match value {
+            "ask" => Ok(GenderPreference::Ask),
+            "default" => Ok(GenderPreference::Default),
+            _ => GENDERS
+                .iter()
+                .find(|(name, _)| *name == value)
+                .map(|(_, gender)| GenderPreference::Always(*gender))
+                .ok_or_else(|| {
+                    Error::new(format!(
+                        "\u{201C}{}\u{201D} isn't a gender. Please use ask, default, woman or man.",
+                        value
+                    ))
+                }),
+        }
+    }
+}
+
+/// Returns the fully-qualified form of the given emoji sequence, i.e. with
+/// any variation selectors it needs, since ZWJ sequences like families and
+/// professions are only shown as a single emoji in that form. Single code
+/// points are returned unchanged, since symbols like `©` are usually wanted
+/// as text rather than as emoji.
+pub fn fully_qualify(text: &str) -> String {
+    if text.chars().nth(1).is_none() {
+        return text.to_string();
+    }
+    emojis::get(text)
+        .map(|emoji| emoji.as_str().to_string())
+        .unwrap_or_else(|| text.to_string())
+}
+
+/// Applies the given skin tone to the given emoji, returning `None` if it
+/// doesn't come in different skin tones or already has one.
+pub fn with_skin_tone(text: &str, tone: SkinTone) -> Option<&'static str> {
+    let emoji = emojis::get(text)?;
+    if emoji.skin_tone() != Some(SkinTone::Default) {
+        return None;
+    }
+    Some(emoji.with_skin_tone(tone)?.as_str())
+}
+
+/// Splits an emoji in a skin tone into the emoji in the default one and the
+/// name of the tone, e.g. 👋🏽 into 👋 and `medium`.
+pub fn split_skin_tone(text: &str) -> Option<(&'static str, &'static str)> {
+    let emoji = emojis::get(text)?;
+    let tone = emoji.skin_tone()?;
+    let (name, _) = SKIN_TONES.iter().find(|(_, other)| *other == tone)?;
+    Some((emoji.with_skin_tone(SkinTone::Default)?.as_str(), name))
+}
+
+/// Returns the skin tone with the given name, e.g. `medium`.
+pub fn find_skin_tone(name: &str) -> Option<SkinTone> {
+    SKIN_TONES
+        .iter()
+        .find(|(other, _)| *other == name)
+        .map(|(_, tone)| *tone)
+}
+
+/// Returns the variants of the given emoji in each skin tone, e.g.
+/// `("medium", "👋🏽")`, or `None` if it doesn't come in different skin tones.
+pub fn get_skin_tone_variants(text: &str) -> Option<Vec<(&'static str, &'static str)>> {
+    let variants: Vec<(&str, &str)> = SKIN_TONES
+        .iter()
+        .filter_map(|(name, tone)| Some((*name, with_skin_tone(text, *tone)?)))
+        .collect();
+    if variants.is_empty() {
+        None
+    } else {
+        Some(variants)
+    }
+}
+
+/// Returns the given emoji of a person as the given gender, e.g. 🏃‍♀️ for 🏃
+/// or 👩‍💻 for 🧑‍💻, or `None` if there's no such emoji. Emoji that already
+/// have a skin tone aren't changed, since the gender is picked first.
+pub fn with_gender(text: &str, gender: Gender) -> Option<&'static str> {
+    let emoji = emojis::get(text)?;
+    if !matches!(emoji.skin_tone(), None | Some(SkinTone::Default)) {
+        return None;
+    }
+    let (person, sign) = match gender {
+        Gender::Woman => ('\u{1F469}', '\u{2640}'),
+        Gender::Man => ('\u{1F468}', '\u{2642}'),
+    };
+    // Emoji starting with the gender-neutral adult, like technologists, have
+    // a woman or man in its place. Others get a female or male sign.
+    let variant = match emoji.as_str().strip_prefix('\u{1F9D1}') {
+        Some(rest) => format!("{}{}", person, rest),
+        None => format!("{}\u{200D}{}\u{FE0F}", emoji.as_str(), sign),
+    };
+    emojis::get(&variant).map(|emoji| emoji.as_str())
+}
+
+/// Returns the variants of the given emoji as each gender, e.g.
+/// `("woman", "🏃‍♀️")`, or `None` if it doesn't come in different genders.
+pub fn get_gender_variants(text: &str) -> Option<Vec<(&'static str, &'static str)>> {
+    let variants: Vec<(&str, &str)> = GENDERS
+        .iter()
+        .filter_map(|(name, gender)| Some((*name, with_gender(text, *gender)?)))
+        .collect();
+    if variants.is_empty() {
+        None
+    } else {
+        Some(variants)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_works() {
+        assert_eq!(
+            SkinTonePreference::parse("ask").unwrap(),
+            SkinTonePreference::Ask
+        );
+        assert_eq!(
+            SkinTonePreference::parse("medium-dark").unwrap(),
+            SkinTonePreference::Always(SkinTone::MediumDark)
+        );
+        assert!(SkinTonePreference::parse("blarg").is_err());
+    }
+
+    #[test]
+    fn test_fully_qualify_works_with_zwj_sequences() {
+        assert_eq!(
+            fully_qualify("\u{1F3F3}\u{200D}\u{1F308}"),
+            "\u{1F3F3}\u{FE0F}\u{200D}\u{1F308}"
+        );
+        assert_eq!(fully_qualify("\u{A9}"), "\u{A9}");
+        assert_eq!(fully_qualify("hello"), "hello");
+    }
+
+    #[test]
+    fn test_split_skin_tone_works() {
+        assert_eq!(
+            split_skin_tone("\u{1F44B}\u{1F3FD}"),
+            Some(("\u{1F44B}", "medium"))
+        );
+        assert_eq!(split_skin_tone("\u{1F44B}"), None);
+        assert_eq!(split_skin_tone("\u{2603}"), None);
+        assert_eq!(find_skin_tone("medium-dark"), Some(SkinTone::MediumDark));
+    }
+
+    #[test]
+    fn test_get_skin_tone_variants_works() {
+        let variants = get_skin_tone_variants("\u{1F44B}").unwrap();
+        assert_eq!(variants.len(), 5);
+        assert_eq!(variants[2], ("medium", "\u{1F44B}\u{1F3FD}"));
+        assert_eq!(get_skin_tone_variants("\u{1F600}"), None);
+        assert_eq!(get_skin_tone_variants("\u{1F44B}\u{1F3FD}"), None);
+    }
+
+    #[test]
+    fn test_with_skin_tone_works_with_zwj_sequences() {
+        // Woman technologist.
+        assert_eq!(
+            with_skin_tone("\u{1F469}\u{200D}\u{1F4BB}", SkinTone::Dark),
+            Some("\u{1F469}\u{1F3FF}\u{200D}\u{1F4BB}")
+        );
+    }
+
+    #[test]
+    fn test_parse_gender_works() {
+        assert_eq!(
+            GenderPreference::parse("woman").unwrap(),
+            GenderPreference::Always(Gender::Woman)
+        );
+        assert_eq!(
+            GenderPreference::parse("default").unwrap(),
+            GenderPreference::Default
+        );
+        assert!(GenderPreference::parse("blarg").is_err());
+    }
+
+    #[test]
+    fn test_get_gender_variants_works() {
+        // Person running.
+        assert_eq!(
+            get_gender_variants("\u{1F3C3}").unwrap(),
+            vec![
+                ("woman", "\u{1F3C3}\u{200D}\u{2640}\u{FE0F}"),
+                ("man", "\u{1F3C3}\u{200D}\u{2642}\u{FE0F}"),
+            ]
+        );
+        assert_eq!(get_gender_variants("\u{1F600}"), None);
+        assert_eq!(
+            get_gender_variants("\u{1F3C3}\u{200D}\u{2640}\u{FE0F}"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_with_gender_replaces_gender_neutral_adults() {
+        // Technologist.
+        assert_eq!(
+            with_gender("\u{1F9D1}\u{200D}\u{1F4BB}", Gender::Woman),
+            Some("\u{1F469}\u{200D}\u{1F4BB}")
+        );
+        assert_eq!(with_gender("\u{1F9D1}", Gender::Man), Some("\u{1F468}"));
+        assert_eq!(with_gender("\u{1F9D1}\u{1F3FD}", Gender::Man), None);
+    }
+}
@@ -0,0 +1,263 @@
+use crate::error::Error;
+
+use super::latex_symbols::{find_latex_symbol, LATEX_SYMBOLS};
+
+/// Characters with superscript forms, taken from the `<super>` decompositions
+/// in the Unicode Character Database. Ones too new for most fonts are left
+/// out.
+const SUPERSCRIPTS: [(char, char); 65] = [
+    ('(', '\u{207D}'),
+    (')', '\u{207E}'),
+    ('+', '\u{207A}'),
+    ('-', '\u{207B}'),
+    ('0', '\u{2070}'),
+    ('1', '\u{B9}'),
+    ('2', '\u{B2}'),
+    ('3', '\u{B3}'),
+    ('4', '\u{2074}'),
+    ('5', '\u{2075}'),
+    ('6', '\u{2076}'),
+    ('7', '\u{2077}'),
+    ('8', '\u{2078}'),
+    ('9', '\u{2079}'),
+    ('=', '\u{207C}'),
+    ('A', '\u{1D2C}'),
+    ('B', '\u{1D2E}'),
+    ('D', '\u{1D30}'),
+    ('E', '\u{1D31}'),
+    ('G', '\u{1D33}'),
+    ('H', '\u{1D34}'),
+    ('I', '\u{1D35}'),
+    ('J', '\u{1D36}'),
+    ('K', '\u{1D37}'),
+    ('L', '\u{1D38}'),
+    ('M', '\u{1D39}'),
+    ('N', '\u{1D3A}'),
+    ('O', '\u{1D3C}'),
+    ('P', '\u{1D3E}'),
+    ('R', '\u{1D3F}'),
+    ('T', '\u{1D40}'),
+    ('U', '\u{1D41}'),
+    ('V', '\u{2C7D}'),
+    ('W', '\u{1D42}'),
+    ('a', '\u{1D43}'),
+    ('b', '\u{1D47}'),
+    ('c', '\u{1D9C}'),
+    ('d', '\u{1D48}'),
+    ('e', '\u{1D49}'),
+    ('f', '\u{1DA0}'),
+    ('g', '\u{1D4D}'),
+    ('h', '\u{2B0}'),
+    ('i', '\u{2071}'),
+    ('j', '\u{2B2}'),
+    ('k', '\u{1D4F}'),
+    ('l', '\u{2E1}'),
+    ('m', '\u{1D50}'),
+    ('n', '\u{207F}'),
+    ('o', '\u{1D52}'),
+    ('p', '\u{1D56}'),
+    ('r', '\u{2B3}'),
+    ('s', '\u{2E2}'),
+    ('t', '\u{1D57}'),
+    ('u', '\u{1D58}'),
+    ('v', '\u{1D5B}'),
+    ('w', '\u{2B7}'),
+    ('x', '\u{2E3}'),
+    ('y', '\u{2B8}'),
+    ('z', '\u{1DBB}'),
+    ('\u{3B2}', '\u{1D5D}'),
+    ('\u{3B3}', '\u{1D5E}'),
+    ('\u{3B4}', '\u{1D5F}'),
+    ('\u{3B8}', '\u{1DBF}'),
+    ('\u{3C6}', '\u{1D60}'),
+    ('\u{3C7}', '\u{1D61}'),
+];
+
+/// Characters with subscript forms, taken from the `<sub>` decompositions in
+/// the Unicode Character Database.
+const SUBSCRIPTS: [(char, char); 37] = [
+    ('(', '\u{208D}'),
+    (')', '\u{208E}'),
+    ('+', '\u{208A}'),
+    ('-', '\u{208B}'),
+    ('0', '\u{2080}'),
+    ('1', '\u{2081}'),
+    ('2', '\u{2082}'),
+    ('3', '\u{2083}'),
+    ('4', '\u{2084}'),
+    ('5', '\u{2085}'),
+    ('6', '\u{2086}'),
+    ('7', '\u{2087}'),
+    ('8', '\u{2088}'),
+    ('9', '\u{2089}'),
+    ('=', '\u{208C}'),
+    ('a', '\u{2090}'),
+    ('e', '\u{2091}'),
+    ('h', '\u{2095}'),
+    ('i', '\u{1D62}'),
+    ('j', '\u{2C7C}'),
+    ('k', '\u{2096}'),
+    ('l', '\u{2097}'),
+    ('m', '\u{2098}'),
+    ('n', '\u{2099}'),
+    ('o', '\u{2092}'),
+    ('p', '\u{209A}'),
+    ('r', '\u{1D63}'),
+    ('s', '\u{209B}'),
+    ('t', '\u{209C}'),
+    ('u', '\u{1D64}'),
+    ('v', '\u{1D65}'),
+    ('x', '\u{2093}'),
+    ('\u{3B2}', '\u{1D66}'),
+    ('\u{3B3}', '\u{1D67}'),
+    ('\u{3C1}', '\u{1D68}'),
+    ('\u{3C6}', '\u{1D69}'),
+    ('\u{3C7}', '\u{1D6A}'),
+];
+
+/// Converts the given text to superscript or subscript using the given
+/// table, failing if any of it has no such form.
+fn convert_script(text: &str, table: &[(char, char)], kind: &str) -> Result<String, Error> {
+    text.chars()
+        .map(|ch| {
+            table
+                .iter()
+                .find(|(normal, _)| *normal == ch)
+                .map(|(_, script)| *script)
+                .ok_or_else(|| {
+                    Error::new(format!(
+                        "\u{201C}{}\u{201D} doesn't have a {} form.",
+                        ch, kind
+                    ))
+                })
+        })
+        .collect()
+}
+
+/// Returns the text of a LaTeX command's argument, which is either a group
+/// in braces like `{10}` or a single character, along with what's left over.
+fn split_argument(text: &str) -> Result<(&str, &str), Error> {
+    if let Some(rest) = text.strip_prefix('{') {
+        let end = rest.find('}').ok_or_else(|| {
+            Error::new("There's a \u{201C}{\u{201D} without a \u{201C}}\u{201D}.")
+        })?;
+        return Ok((&rest[..end], &rest[end + 1..]));
+    }
+    match text.chars().next() {
+        Some(ch) => Ok(text.split_at(ch.len_utf8())),
+        None => Err(Error::new(
+            "\u{201C}^\u{201D} and \u{201C}_\u{201D} need something after them.",
+        )),
+    }
+}
+
+/// Converts a short bit of LaTeX math to plain text, e.g. `\alpha \leq x^2`
+/// to `α ≤ x²`. Commands are replaced with the characters they stand for, and
+/// anything after `^` or `_` is converted to superscript or subscript.
+pub fn convert_latex(text: &str) -> Result<String, Error> {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(ch) = rest.chars().next() {
+        rest = &rest[ch.len_utf8()..];
+        match ch {
+            '\\' => {
+                // Commands are either a run of letters or a single symbol.
+                let len = match rest.find(|c: char| !c.is_ascii_alphabetic()) {
+                    Some(0) => rest.chars().next().map_or(0, char::len_utf8),
+                    Some(len) => len,
+                    None => rest.len(),
+                };
+                let name = &rest[..len];
+                rest = &rest[len..];
+                let symbol = find_latex_symbol(name).ok_or_else(|| {
+                    Error::new(format!(
+                        "\u{201C}\\{}\u{201D} isn't a LaTeX command I know.",
+                        name
+                    ))
+                })?;
+                result.push_str(symbol);
+            }
+            '^' | '_' => {
+                let (argument, remaining) = split_argument(rest)?;
+                rest = remaining;
+                let argument = convert_latex(argument)?;
+                result.push_str(&if ch == '^' {
+                    convert_script(&argument, &SUPERSCRIPTS, "superscript")?
+                } else {
+                    convert_script(&argument, &SUBSCRIPTS, "subscript")?
+                });
+            }
+            _ => result.push(ch),
+        }
+    }
+    Ok(result)
+}
+
+/// Returns the LaTeX command names containing the given text, best matches
+/// first, along with the characters they stand for.
+pub fn find_latex_names(
+    partial_name: &str,
+    max_results: usize,
+) -> Vec<(&'static str, &'static str)> {
+    let mut found: Vec<(&str, &str)> = LATEX_SYMBOLS
+        .iter()
+        .filter(|(name, _)| name.contains(partial_name))
+        .copied()
+        .collect();
+    found.sort_by_key(|(name, _)| (name.find(partial_name), name.len(), *name));
+    found.truncate(max_results);
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_latex_replaces_commands() {
+        assert_eq!(
+            convert_latex("\\alpha \\leq \\beta\\rightarrow x").unwrap(),
+            "\u{3B1} \u{2264} \u{3B2}\u{2192} x"
+        );
+    }
+
+    #[test]
+    fn test_convert_latex_converts_scripts() {
+        assert_eq!(convert_latex("x^2").unwrap(), "x\u{B2}");
+        assert_eq!(convert_latex("H_2O").unwrap(), "H\u{2082}O");
+        assert_eq!(
+            convert_latex("e^{-in}").unwrap(),
+            "e\u{207B}\u{2071}\u{207F}"
+        );
+    }
+
+    fn error(text: &str) -> String {
+        match convert_latex(text) {
+            Err(Error::Other(message)) => message.to_string(),
+            other => panic!("Expected an error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_convert_latex_rejects_bad_input() {
+        assert_eq!(
+            error("\\blarg"),
+            "\u{201C}\\blarg\u{201D} isn't a LaTeX command I know."
+        );
+        assert_eq!(
+            error("x_q"),
+            "\u{201C}q\u{201D} doesn't have a subscript form."
+        );
+        assert_eq!(
+            error("x^{2"),
+            "There's a \u{201C}{\u{201D} without a \u{201C}}\u{201D}."
+        );
+    }
+
+    #[test]
+    fn test_find_latex_names_works() {
+        let found = find_latex_names("alph", 2);
+        assert_eq!(found, vec![("alpha", "\u{3B1}")]);
+        assert_eq!(find_latex_symbol("rightarrow"), Some("\u{2192}"));
+    }
+}
@@ -0,0 +1,12 @@
+// This defines `LATEX_SYMBOLS`, which pairs LaTeX math command names (without
+// their backslashes) with the characters they stand for, sorted by name.
+include!(concat!(env!("OUT_DIR"), "/latex_symbols.rs"));
+
+/// Returns the character the given LaTeX command stands for, e.g. `α` for
+/// `alpha`.
+pub fn find_latex_symbol(name: &str) -> Option<&'static str> {
+    LATEX_SYMBOLS
+        .binary_search_by_key(&name, |(other, _)| other)
+        .ok()
+        .map(|idx| LATEX_SYMBOLS[idx].1)
+}
@@ -0,0 +1,275 @@
+use std::convert::TryFrom;
+
+use crate::{autocomplete_map::autocomplete_names, error::Error};
+
+use super::cldr_annotations::{get_locale, Annotation};
+use super::emoji::{find_skin_tone, fully_qualify, split_skin_tone, with_skin_tone};
+
+const DEFAULT_LOCALE: &str = "en";
+
+/// A character that was found by one of its CLDR keywords rather than its
+/// name, e.g. `laugh` for 😂.
+#[derive(Debug, PartialEq)]
+pub struct KeywordMatch {
+    pub text: &'static str,
+    pub name: &'static str,
+    pub keyword: &'static str,
+}
+
+/// The CLDR annotations for the locales characters can be found in, e.g.
+/// English and German.
+#[derive(Clone)]
+pub struct CharacterNames {
+    locales: Vec<&'static [Annotation]>,
+}
+
+impl Default for CharacterNames {
+    fn default() -> Self {
+        CharacterNames::new(&[DEFAULT_LOCALE]).0
+    }
+}
+
+impl CharacterNames {
+    /// Creates a set of names for the given locales, in order of preference,
+    /// also returning any locales we don't have annotations for.
+    pub fn new<'a>(locales: &[&'a str]) -> (Self, Vec<&'a str>) {
+        let mut unknown = vec![];
+        let mut names = CharacterNames { locales: vec![] };
+        for locale in locales {
+            match get_locale(locale) {
+                Some(annotations) => names.locales.push(annotations),
+                None => unknown.push(*locale),
+            }
+        }
+        (names, unknown)
+    }
+
+    fn annotations(&self) -> impl Iterator<Item = &'static Annotation> + '_ {
+        self.locales
+            .iter()
+            .flat_map(|annotations| annotations.iter())
+    }
+
+    /// Returns the CLDR short name of every character.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.annotations().map(|(_, name, _)| *name)
+    }
+
+    /// Returns the text with the given CLDR short name, e.g. `snowman`. This
+    /// may consist of more than one code point, as many emoji do.
+    pub fn find_cldr_annotation(&self, name: &str) -> Option<&'static str> {
+        self.annotations()
+            .find(|(_, other, _)| *other == name)
+            .map(|(ch, _, _)| *ch)
+    }
+
+    /// Returns the CLDR short name of the given text, e.g. `snowman` for ☃.
+    /// Variation selectors are ignored, since CLDR leaves them out. Emoji in
+    /// a skin tone get the name CLDR derives for them, e.g. `waving hand:
+    /// medium skin tone` for 👋🏽.
+    pub fn find_name(&self, text: &str) -> Option<String> {
+        let stripped = text.replace('\u{FE0F}', "");
+        if let Some((_, name, _)) = self.annotations().find(|(ch, _, _)| *ch == stripped) {
+            return Some(name.to_string());
+        }
+        let (text, tone) = split_skin_tone(text)?;
+        Some(format!("{}: {} skin tone", self.find_name(text)?, tone))
+    }
+
+    /// Returns the emoji with the given name in a skin tone, as returned by
+    /// `find_name`.
+    fn find_in_skin_tone(&self, name: &str) -> Option<String> {
+        let (name, tone) = name.rsplit_once(": ")?;
+        let tone = find_skin_tone(tone.strip_suffix(" skin tone")?)?;
+        let text = fully_qualify(self.find_cldr_annotation(name)?);
+        with_skin_tone(&text, tone).map(String::from)
+    }
+
+    /// Finds characters that have a CLDR keyword matching the given input,
+    /// best matches first. Characters whose names match are left out, since
+    /// they'll already be found by name.
+    pub fn find_keyword_matches(&self, input: &str, max_results: usize) -> Vec<KeywordMatch> {
+        // CLDR names and keywords are lowercase.
+        let input = &input.to_lowercase();
+        let mut found: Vec<KeywordMatch> = self
+            .annotations()
+            .filter(|(_, name, _)| !name.contains(input.as_str()))
+            .filter_map(|(text, name, keywords)| {
+                let (keyword, _) = autocomplete_names(input, keywords.iter(), 1).pop()?;
+                Some(KeywordMatch {
+                    text,
+                    name,
+                    keyword,
+                })
+            })
+            .collect();
+        found.sort_by_key(|m| (m.keyword.find(input), m.keyword, m.name));
+        found.truncate(max_results);
+        found
+    }
+
+    /// Finds the text the given name refers to, which can be a CLDR short
+    /// name (e.g. `snowman`), a code point (e.g. `u+2603` or `0x2603`) or an
+    /// official Unicode character name (e.g. `word joiner`). Returns
+    /// `Ok(None)` if it doesn't refer to any character, and an error if it's
+    /// a code point that doesn't.
+    pub fn find_character(&self, name: &str) -> Result<Option<String>, Error> {
+        if let Some(text) = self.find_cldr_annotation(name) {
+            return Ok(Some(text.to_string()));
+        }
+        if let Some(text) = self.find_in_skin_tone(name) {
+            return Ok(Some(text));
+        }
+        if let Some(result) = parse_code_point(name) {
+            return result.map(|ch| Some(ch.to_string()));
+        }
+        Ok(unicode_names2::character(name).map(|ch| ch.to_string()))
+    }
+}
+
+fn is_private_use(ch: char) -> bool {
+    matches!(ch as u32, 0xE000..=0xF8FF | 0xF0000..=0xFFFFD | 0x100000..=0x10FFFD)
+}
+
+/// Parses a code point written like `u+2603` or `0x1F600`. Returns `None` if
+/// the text isn't written like a code point at all.
+fn parse_code_point(text: &str) -> Option<Result<char, Error>> {
+    let lowercase = text.to_lowercase();
+    let hex = lowercase
+        .strip_prefix("u+")
+        .or_else(|| lowercase.strip_prefix("0x"))?;
+    let Ok(value) = u32::from_str_radix(hex, 16) else {
+        return Some(Err(Error::new(format!(
+            "\u{201C}{}\u{201D} isn't a valid code point.",
+            text
+        ))));
+    };
+    let code_point = format!("U+{:04X}", value);
+    let Ok(ch) = char::try_from(value) else {
+        return Some(Err(Error::new(format!(
+            "{} isn't a valid code point.",
+            code_point
+        ))));
+    };
+    // Characters without names are either unassigned, or ones that are
+    // named by rule rather than in the table, like control characters.
+    if unicode_names2::name(ch).is_none() && !ch.is_control() && !is_private_use(ch) {
+        return Some(Err(Error::new(format!(
+            "{} isn't assigned to any character.",
+            code_point
+        ))));
+    }
+    Some(Ok(ch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(name: &str) -> Option<String> {
+        CharacterNames::default().find_character(name).unwrap()
+    }
+
+    fn assert_rejected(name: &str, message: &str) {
+        let error = CharacterNames::default()
+            .find_character(name)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains(message),
+            "{} doesn't contain {}",
+            error,
+            message
+        );
+    }
+
+    #[test]
+    fn test_find_character_works_with_cldr_names() {
+        assert_eq!(find("snowman"), Some(String::from("\u{2603}")));
+    }
+
+    #[test]
+    fn test_find_character_works_with_code_points() {
+        assert_eq!(find("u+2603"), Some(String::from("\u{2603}")));
+        assert_eq!(find("U+2603"), Some(String::from("\u{2603}")));
+        assert_eq!(find("0x1f600"), Some(String::from("\u{1F600}")));
+        assert_eq!(find("u+e000"), Some(String::from("\u{E000}")));
+        assert_eq!(find("u+0007"), Some(String::from("\u{7}")));
+    }
+
+    #[test]
+    fn test_find_character_works_with_unicode_names() {
+        assert_eq!(find("word joiner"), Some(String::from("\u{2060}")));
+        assert_eq!(
+            find("box drawings light vertical"),
+            Some(String::from("\u{2502}"))
+        );
+    }
+
+    #[test]
+    fn test_find_name_works_with_skin_tones() {
+        let names = CharacterNames::default();
+        let name = "waving hand: medium skin tone";
+        assert_eq!(names.find_name("\u{1F44B}\u{1F3FD}").as_deref(), Some(name));
+        assert_eq!(find(name), Some(String::from("\u{1F44B}\u{1F3FD}")));
+        assert_eq!(find("waving hand: purple skin tone"), None);
+    }
+
+    #[test]
+    fn test_find_character_returns_none_for_unknown_names() {
+        assert_eq!(find("hello there"), None);
+    }
+
+    #[test]
+    fn test_find_keyword_matches_works() {
+        let found = CharacterNames::default().find_keyword_matches("laugh", 100);
+        assert!(found.contains(&KeywordMatch {
+            text: "\u{1F602}",
+            name: "face with tears of joy",
+            keyword: "laugh",
+        }));
+    }
+
+    #[test]
+    fn test_find_keyword_matches_ignores_case() {
+        let names = CharacterNames::default();
+        assert_eq!(
+            names.find_keyword_matches("Laugh", 100),
+            names.find_keyword_matches("laugh", 100)
+        );
+    }
+
+    #[test]
+    fn test_find_keyword_matches_leaves_out_name_matches() {
+        let found = CharacterNames::default().find_keyword_matches("snowman", 100);
+        assert!(found.iter().all(|m| !m.name.contains("snowman")));
+    }
+
+    #[test]
+    fn test_find_character_works_with_other_locales() {
+        let (names, unknown) = CharacterNames::new(&["de", "xx"]);
+        assert_eq!(unknown, vec!["xx"]);
+        assert_eq!(
+            names.find_character("schneemann im schnee").unwrap(),
+            Some(String::from("\u{2603}"))
+        );
+        assert_eq!(names.find_cldr_annotation("snowman"), None);
+    }
+
+    #[test]
+    fn test_names_can_be_typed_without_accents() {
+        let (names, _) = CharacterNames::new(&["de"]);
+        assert_eq!(
+            names.find_character("leicht lachelndes gesicht").unwrap(),
+            Some(String::from("\u{1F642}"))
+        );
+    }
+
+    #[test]
+    fn test_find_character_rejects_bad_code_points() {
+        assert_rejected("u+zz", "\u{201C}u+zz\u{201D} isn't a valid code point.");
+        assert_rejected("u+d800", "U+D800 isn't a valid code point.");
+        assert_rejected("0x110000", "U+110000 isn't a valid code point.");
+        assert_rejected("u+0378", "U+0378 isn't assigned to any character.");
+    }
+}
@@ -1,21 +1,908 @@
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
 use crate::{
-    command::SimpleCommand,
+    autocomplete_map::autocomplete_names,
+    command::{ArgumentSuggestion, Command, CommandOutput, SimpleCommand},
     error::Error,
+    prompt::Prompt,
     ui::{UserInterface, UserInterfacePlugin},
 };
 
-use super::cldr_annotations::CLDR_ANNOTATIONS;
+use super::describe::describe_text;
+use super::digraphs::Digraphs;
+use super::emoji::{
+    fully_qualify, get_gender_variants, get_skin_tone_variants, with_gender, with_skin_tone,
+    GenderPreference, SkinTonePreference,
+};
+use super::latex::{convert_latex, find_latex_names};
+use super::latex_symbols::find_latex_symbol;
+use super::lookup::CharacterNames;
+use super::usage::{CharacterUsage, SharedUsage};
 
 #[derive(Default)]
 pub struct InsertUnicodeCharactersPlugin;
 
 impl UserInterfacePlugin for InsertUnicodeCharactersPlugin {
+    fn name(&self) -> String {
+        String::from("unicode")
+    }
+
     fn init(&mut self, ui: &mut UserInterface) -> Result<(), Error> {
-        for (ch, name) in &CLDR_ANNOTATIONS {
-            let name = format!("insert {}", name);
-            let cmd = SimpleCommand::new(name, move |_ui| crate::system::type_char(ch));
-            ui.add_command(cmd.into_box());
+        let locales: Vec<String> = ui
+            .config()
+            .get_character_locales()
+            .into_iter()
+            .map(String::from)
+            .collect();
+        // Showing a message replaces the previous one, so these are all shown
+        // together once everything is loaded.
+        let mut warnings = vec![];
+        let skin_tone = match ui.config().get_skin_tone().map(SkinTonePreference::parse) {
+            Some(Ok(skin_tone)) => skin_tone,
+            Some(Err(error)) => {
+                warnings.push(format!("Problem with the skin_tone setting: {}", error));
+                SkinTonePreference::default()
+            }
+            None => SkinTonePreference::default(),
+        };
+        let gender = match ui.config().get_gender().map(GenderPreference::parse) {
+            Some(Ok(gender)) => gender,
+            Some(Err(error)) => {
+                warnings.push(format!("Problem with the gender setting: {}", error));
+                GenderPreference::default()
+            }
+            None => GenderPreference::default(),
+        };
+        ui.load_in_background(
+            "unicode",
+            move || LoadedData::load(&locales),
+            move |ui, data| {
+                warnings.extend(data.warnings);
+                if !warnings.is_empty() {
+                    ui.show_message(warnings.join("\n"))?;
+                }
+                add_commands(ui, data.names, data.digraphs, data.usage, skin_tone, gender);
+                Ok(())
+            },
+        )
+    }
+}
+
+/// Everything the plugin's commands need that takes a while to load, which
+/// is loaded on a worker thread so that it doesn't hold up Enso's startup.
+struct LoadedData {
+    names: CharacterNames,
+    digraphs: Digraphs,
+    usage: CharacterUsage,
+    /// Problems found while loading, which are shown to the user.
+    warnings: Vec<String>,
+}
+
+impl LoadedData {
+    fn load(locales: &[String]) -> Result<Self, Error> {
+        let mut warnings = vec![];
+        let names = if locales.is_empty() {
+            CharacterNames::default()
+        } else {
+            let locales: Vec<&str> = locales.iter().map(String::as_str).collect();
+            let (names, unknown) = CharacterNames::new(&locales);
+            if !unknown.is_empty() {
+                warnings.push(format!(
+                    "Characters can't be inserted by name in these locales yet: {}",
+                    unknown.join(", ")
+                ));
+            }
+            names
+        };
+        let (digraphs, digraph_warnings) = Digraphs::load()?;
+        if !digraph_warnings.is_empty() {
+            warnings.push(format!(
+                "Problems occurred while parsing your digraphs:\n{}",
+                digraph_warnings.join("\n")
+            ));
+        }
+        let usage = match CharacterUsage::load() {
+            Ok(usage) => usage,
+            Err(error) => {
+                warnings.push(format!("Problem loading your character usage: {}", error));
+                CharacterUsage::default()
+            }
+        };
+        Ok(LoadedData {
+            names,
+            digraphs,
+            usage,
+            warnings,
+        })
+    }
+}
+
+fn add_commands(
+    ui: &mut UserInterface,
+    names: CharacterNames,
+    digraphs: Digraphs,
+    usage: CharacterUsage,
+    skin_tone: SkinTonePreference,
+    gender: GenderPreference,
+) {
+    let usage: SharedUsage = Arc::new(Mutex::new(usage));
+    ui.add_command(Box::new(InsertDigraphCommand {
+        digraphs,
+        usage: usage.clone(),
+    }));
+    ui.add_command(Box::new(TexCommand {
+        usage: usage.clone(),
+    }));
+    ui.add_command(Box::new(DescribeCommand {
+        names: names.clone(),
+    }));
+    ui.add_command(Box::new(PinCommand {
+        names: names.clone(),
+        usage: usage.clone(),
+    }));
+    ui.add_command(Box::new(UnpinCommand {
+        names: names.clone(),
+        usage: usage.clone(),
+    }));
+    ui.add_command(Box::new(InsertCommand {
+        names,
+        skin_tone,
+        gender,
+        usage,
+    }));
+}
+
+/// Inserts the character with the given name or code point, e.g. `insert
+/// snowman` or `insert u+2603`, or LaTeX command, e.g. `insert \alpha`. If
+/// there isn't one, the argument is inserted as-is, e.g. `insert hello`.
+///
+/// Emoji that come in several skin tones are inserted in the one chosen by
+/// the `skin_tone` setting, and if there isn't one, the user is asked which
+/// one they'd like. Emoji of people that also come as a woman and a man work
+/// the same way with the `gender` setting, which is asked about first.
+///
+/// Pinned and frequently inserted characters are suggested first, and typing
+/// nothing after `insert` lists the pinned and recently inserted ones.
+#[derive(Clone)]
+struct InsertCommand {
+    names: CharacterNames,
+    skin_tone: SkinTonePreference,
+    gender: GenderPreference,
+    usage: SharedUsage,
+}
+
+impl InsertCommand {
+    /// Like `find_text`, but puts emoji in the preferred gender and skin tone.
+    fn find_character(&self, argument: &str) -> Result<Option<String>, Error> {
+        let Some(text) = find_text(&self.names, argument)? else {
+            return Ok(None);
+        };
+        let text = match self.gender {
+            GenderPreference::Always(gender) => {
+                with_gender(&text, gender).map(String::from).unwrap_or(text)
+            }
+            _ => text,
+        };
+        Ok(Some(match self.skin_tone {
+            SkinTonePreference::Always(tone) => with_skin_tone(&text, tone)
+                .map(String::from)
+                .unwrap_or(text),
+            _ => text,
+        }))
+    }
+}
+
+impl Command for InsertCommand {
+    fn name(&self) -> String {
+        String::from("insert")
+    }
+
+    fn execute(&mut self, _ui: &mut UserInterface) -> Result<CommandOutput, Error> {
+        Err(Error::new(
+            "Please tell me what to insert, e.g. \u{201C}insert snowman\u{201D}.",
+        ))
+    }
+
+    fn takes_argument(&self) -> bool {
+        true
+    }
+
+    fn execute_with_argument(
+        &mut self,
+        _ui: &mut UserInterface,
+        argument: &str,
+    ) -> Result<CommandOutput, Error> {
+        let Some(text) = self.find_character(argument)? else {
+            // Pinned and recently inserted text without a name is suggested
+            // as itself, e.g. a sequence CLDR doesn't cover.
+            if self.usage.lock().unwrap().is_used(argument) {
+                record_usage(&self.usage, argument);
+            }
+            return Ok(CommandOutput::InsertText(argument.to_string()));
+        };
+        if self.gender == GenderPreference::Ask {
+            if let Some(variants) = get_gender_variants(&text) {
+                // As with skin tones, the default is listed first.
+                let mut choices =
+                    vec![gender_choice("default", &text, self.skin_tone, &self.usage)];
+                for (name, variant) in variants {
+                    choices.push(gender_choice(name, variant, self.skin_tone, &self.usage));
+                }
+                return Ok(CommandOutput::Prompt(Prompt::choose(
+                    "Which gender would you like?",
+                    choices,
+                )));
+            }
+        }
+        Ok(insert_in_skin_tone(&text, self.skin_tone, &self.usage))
+    }
+
+    fn preview(&self, argument: Option<&str>) -> Option<String> {
+        let argument = argument?;
+        match self.find_character(argument) {
+            Ok(Some(text)) => {
+                let asks_gender =
+                    self.gender == GenderPreference::Ask && get_gender_variants(&text).is_some();
+                let asks_skin_tone = self.skin_tone == SkinTonePreference::Ask
+                    && get_skin_tone_variants(&text).is_some();
+                let choice = match (asks_gender, asks_skin_tone) {
+                    (true, true) => " in a gender and skin tone of your choice",
+                    (true, false) => " in a gender of your choice",
+                    (false, true) => " in a skin tone of your choice",
+                    (false, false) => "",
+                };
+                Some(format!("Insert \u{201C}{}\u{201D}{}.", text, choice))
+            }
+            Ok(None) => Some(format!("Insert \u{201C}{}\u{201D}.", argument)),
+            Err(Error::Other(message)) => Some(message.to_string()),
+            Err(_) => None,
+        }
+    }
+
+    fn suggest_arguments(
+        &self,
+        partial_argument: &str,
+        max_results: usize,
+    ) -> Vec<ArgumentSuggestion> {
+        if let Some(partial_name) = partial_argument.strip_prefix('\\') {
+            return suggest_latex_names(partial_name, "\\", max_results);
+        }
+        let usage = self.usage.lock().unwrap();
+        if partial_argument.is_empty() {
+            return usage
+                .recent()
+                .into_iter()
+                .take(max_results)
+                .map(|text| suggest_used(&self.names, &usage, text, vec![]))
+                .collect();
+        }
+        let mut suggs = vec![];
+        for text in usage.ranked() {
+            let argument = get_usage_argument(&self.names, text);
+            let found = autocomplete_names(partial_argument, std::iter::once(&argument), 1);
+            if let Some((_, matches)) = found.into_iter().next() {
+                suggs.push(suggest_used(&self.names, &usage, text, matches));
+            }
+        }
+        suggs.truncate(max_results);
+        // Code points and official names aren't in the table we autocomplete
+        // from, so if that's what the user typed, show them what it is.
+        if self.names.find_cldr_annotation(partial_argument).is_none() {
+            if let Ok(Some(text)) = self.names.find_character(partial_argument) {
+                push_new_suggestion(
+                    &mut suggs,
+                    ArgumentSuggestion {
+                        argument: partial_argument.to_string(),
+                        matches: vec![0..partial_argument.len()],
+                        label: Some(text),
+                    },
+                );
+            }
+        }
+        let names: Vec<&str> = self.names.names().collect();
+        for (name, matches) in autocomplete_names(partial_argument, names.iter(), max_results) {
+            push_new_suggestion(
+                &mut suggs,
+                ArgumentSuggestion {
+                    argument: name.to_string(),
+                    matches,
+                    label: self.names.find_cldr_annotation(name).map(String::from),
+                },
+            );
+        }
+        // Keyword matches rank below name matches. We still suggest the name,
+        // so the label says which keyword matched.
+        let remaining = max_results.saturating_sub(suggs.len());
+        for found in self.names.find_keyword_matches(partial_argument, remaining) {
+            push_new_suggestion(
+                &mut suggs,
+                ArgumentSuggestion {
+                    argument: found.name.to_string(),
+                    matches: vec![],
+                    label: Some(format!("{} ({})", found.text, found.keyword)),
+                },
+            );
+        }
+        suggs.truncate(max_results);
+        suggs
+    }
+
+    fn suggests_characters(&self) -> bool {
+        true
+    }
+}
+
+/// Returns the text with the given name, code point or LaTeX command, making
+/// sure emoji sequences are complete.
+fn find_text(names: &CharacterNames, argument: &str) -> Result<Option<String>, Error> {
+    if let Some(symbol) = argument.strip_prefix('\\').and_then(find_latex_symbol) {
+        return Ok(Some(symbol.to_string()));
+    }
+    Ok(names
+        .find_character(argument)?
+        .map(|text| fully_qualify(&text)))
+}
+
+/// Returns what to type after `insert` to insert the given text again, which
+/// is its name if it has one, e.g. `snowman` for ☃ or `waving hand: medium
+/// skin tone` for 👋🏽, or otherwise the text itself.
+fn get_usage_argument(names: &CharacterNames, text: &str) -> String {
+    names.find_name(text).unwrap_or_else(|| text.to_string())
+}
+
+/// Suggests inserting the given pinned or recently inserted text again.
+fn suggest_used(
+    names: &CharacterNames,
+    usage: &CharacterUsage,
+    text: &str,
+    matches: Vec<Range<usize>>,
+) -> ArgumentSuggestion {
+    let label = if usage.is_pinned(text) {
+        format!("{} (pinned)", text)
+    } else {
+        text.to_string()
+    };
+    ArgumentSuggestion {
+        argument: get_usage_argument(names, text),
+        matches,
+        label: Some(label),
+    }
+}
+
+/// Adds the given suggestion unless one with the same argument is already
+/// there, e.g. because it was suggested for having been used recently.
+fn push_new_suggestion(suggs: &mut Vec<ArgumentSuggestion>, sugg: ArgumentSuggestion) {
+    if !suggs.iter().any(|other| other.argument == sugg.argument) {
+        suggs.push(sugg);
+    }
+}
+
+/// Notes that the given text was inserted. This isn't worth failing the
+/// insertion over, so problems saving it are only logged.
+fn record_usage(usage: &SharedUsage, text: &str) {
+    if let Err(error) = usage.lock().unwrap().record(text) {
+        eprintln!("Unable to save character usage: {}", error);
+    }
+}
+
+/// Pins a character so that it's always suggested first by `insert`, e.g.
+/// `pin character snowman`.
+#[derive(Clone)]
+struct PinCommand {
+    names: CharacterNames,
+    usage: SharedUsage,
+}
+
+impl Command for PinCommand {
+    fn name(&self) -> String {
+        String::from("pin character")
+    }
+
+    fn execute(&mut self, _ui: &mut UserInterface) -> Result<CommandOutput, Error> {
+        Err(Error::new(
+            "Please tell me what to pin, e.g. \u{201C}pin character snowman\u{201D}.",
+        ))
+    }
+
+    fn takes_argument(&self) -> bool {
+        true
+    }
+
+    fn execute_with_argument(
+        &mut self,
+        _ui: &mut UserInterface,
+        argument: &str,
+    ) -> Result<CommandOutput, Error> {
+        let text = find_text(&self.names, argument)?.unwrap_or_else(|| argument.to_string());
+        let message = if self.usage.lock().unwrap().pin(&text)? {
+            format!("Pinned \u{201C}{}\u{201D}.", text)
+        } else {
+            format!("\u{201C}{}\u{201D} is already pinned.", text)
+        };
+        Ok(CommandOutput::ShowMessage(message))
+    }
+
+    fn preview(&self, argument: Option<&str>) -> Option<String> {
+        let argument = argument?;
+        match find_text(&self.names, argument) {
+            Ok(text) => Some(format!(
+                "Pin \u{201C}{}\u{201D}.",
+                text.as_deref().unwrap_or(argument)
+            )),
+            Err(Error::Other(message)) => Some(message.to_string()),
+            Err(_) => None,
+        }
+    }
+
+    fn suggest_arguments(
+        &self,
+        partial_argument: &str,
+        max_results: usize,
+    ) -> Vec<ArgumentSuggestion> {
+        let names: Vec<&str> = self.names.names().collect();
+        autocomplete_names(partial_argument, names.iter(), max_results)
+            .into_iter()
+            .map(|(name, matches)| ArgumentSuggestion {
+                argument: name.to_string(),
+                matches,
+                label: self.names.find_cldr_annotation(name).map(String::from),
+            })
+            .collect()
+    }
+
+    fn suggests_characters(&self) -> bool {
+        true
+    }
+}
+
+/// Unpins a character pinned with `pin character`.
+#[derive(Clone)]
+struct UnpinCommand {
+    names: CharacterNames,
+    usage: SharedUsage,
+}
+
+impl Command for UnpinCommand {
+    fn name(&self) -> String {
+        String::from("unpin character")
+    }
+
+    fn execute(&mut self, _ui: &mut UserInterface) -> Result<CommandOutput, Error> {
+        Err(Error::new(
+            "Please tell me what to unpin, e.g. \u{201C}unpin character snowman\u{201D}.",
+        ))
+    }
+
+    fn takes_argument(&self) -> bool {
+        true
+    }
+
+    fn execute_with_argument(
+        &mut self,
+        _ui: &mut UserInterface,
+        argument: &str,
+    ) -> Result<CommandOutput, Error> {
+        let text = find_text(&self.names, argument)?.unwrap_or_else(|| argument.to_string());
+        if self.usage.lock().unwrap().unpin(&text)? {
+            Ok(CommandOutput::ShowMessage(format!(
+                "Unpinned \u{201C}{}\u{201D}.",
+                text
+            )))
+        } else {
+            Err(Error::new(format!(
+                "\u{201C}{}\u{201D} isn't pinned.",
+                text
+            )))
         }
-        Ok(())
+    }
+
+    fn suggest_arguments(
+        &self,
+        partial_argument: &str,
+        max_results: usize,
+    ) -> Vec<ArgumentSuggestion> {
+        let usage = self.usage.lock().unwrap();
+        let arguments: Vec<(String, &str)> = usage
+            .pinned()
+            .iter()
+            .map(|text| (get_usage_argument(&self.names, text), text.as_str()))
+            .collect();
+        let names: Vec<&str> = arguments.iter().map(|(name, _)| name.as_str()).collect();
+        let found = if partial_argument.is_empty() {
+            names.iter().map(|name| (*name, vec![])).collect()
+        } else {
+            autocomplete_names(partial_argument, names.iter(), max_results)
+        };
+        found
+            .into_iter()
+            .take(max_results)
+            .map(|(name, matches)| ArgumentSuggestion {
+                argument: name.to_string(),
+                matches,
+                label: arguments
+                    .iter()
+                    .find(|(other, _)| other == name)
+                    .map(|(_, text)| text.to_string()),
+            })
+            .collect()
+    }
+
+    fn suggests_characters(&self) -> bool {
+        true
+    }
+}
+
+/// Converts a short bit of LaTeX math to plain text and inserts it, e.g.
+/// `tex \alpha \leq x^2` inserts `α ≤ x²`. A lone command doesn't need its
+/// backslash, e.g. `tex alpha`.
+#[derive(Clone)]
+struct TexCommand {
+    usage: SharedUsage,
+}
+
+impl TexCommand {
+    fn convert(argument: &str) -> Result<String, Error> {
+        match find_latex_symbol(argument) {
+            Some(symbol) => Ok(symbol.to_string()),
+            None => convert_latex(argument),
+        }
+    }
+}
+
+impl Command for TexCommand {
+    fn name(&self) -> String {
+        String::from("tex")
+    }
+
+    fn execute(&mut self, _ui: &mut UserInterface) -> Result<CommandOutput, Error> {
+        Err(Error::new(
+            "Please tell me what to convert, e.g. \u{201C}tex \\alpha^2\u{201D}.",
+        ))
+    }
+
+    fn takes_argument(&self) -> bool {
+        true
+    }
+
+    fn execute_with_argument(
+        &mut self,
+        _ui: &mut UserInterface,
+        argument: &str,
+    ) -> Result<CommandOutput, Error> {
+        let text = TexCommand::convert(argument)?;
+        record_usage(&self.usage, &text);
+        Ok(CommandOutput::InsertText(text))
+    }
+
+    fn preview(&self, argument: Option<&str>) -> Option<String> {
+        match TexCommand::convert(argument?) {
+            Ok(text) => Some(format!("Insert \u{201C}{}\u{201D}.", text)),
+            Err(Error::Other(message)) => Some(message.to_string()),
+            Err(_) => None,
+        }
+    }
+
+    fn suggest_arguments(
+        &self,
+        partial_argument: &str,
+        max_results: usize,
+    ) -> Vec<ArgumentSuggestion> {
+        // Names are only suggested while a single command is being typed.
+        if partial_argument.is_empty()
+            || partial_argument.contains(|c: char| !c.is_ascii_alphabetic() && c != '\\')
+        {
+            return vec![];
+        }
+        match partial_argument.strip_prefix('\\') {
+            Some(partial_name) => suggest_latex_names(partial_name, "\\", max_results),
+            None => suggest_latex_names(partial_argument, "", max_results),
+        }
+    }
+
+    fn suggests_characters(&self) -> bool {
+        true
+    }
+}
+
+/// Suggests LaTeX command names containing the given text, each with the
+/// given prefix, e.g. a backslash if the user typed one.
+fn suggest_latex_names(
+    partial_name: &str,
+    prefix: &str,
+    max_results: usize,
+) -> Vec<ArgumentSuggestion> {
+    find_latex_names(partial_name, max_results)
+        .into_iter()
+        .map(|(name, symbol)| {
+            let start = prefix.len() + name.find(partial_name).unwrap_or(0);
+            ArgumentSuggestion {
+                argument: format!("{}{}", prefix, name),
+                matches: vec![start..start + partial_name.len()],
+                label: Some(symbol.to_string()),
+            }
+        })
+        .collect()
+}
+
+/// Inserts the character with the given RFC 1345 digraph, e.g. `insert
+/// digraph e'` for é. Digraphs can be added or changed in
+/// `~/.enso/digraphs.txt`.
+#[derive(Clone)]
+struct InsertDigraphCommand {
+    digraphs: Digraphs,
+    usage: SharedUsage,
+}
+
+impl Command for InsertDigraphCommand {
+    fn name(&self) -> String {
+        String::from("insert digraph")
+    }
+
+    fn execute(&mut self, _ui: &mut UserInterface) -> Result<CommandOutput, Error> {
+        Err(Error::new(
+            "Please tell me which digraph to insert, e.g. \u{201C}insert digraph e'\u{201D}.",
+        ))
+    }
+
+    fn takes_argument(&self) -> bool {
+        true
+    }
+
+    fn execute_with_argument(
+        &mut self,
+        _ui: &mut UserInterface,
+        argument: &str,
+    ) -> Result<CommandOutput, Error> {
+        match self.digraphs.get(argument) {
+            Some(text) => {
+                record_usage(&self.usage, text);
+                Ok(CommandOutput::InsertText(text.to_string()))
+            }
+            None => Err(Error::new(format!(
+                "\u{201C}{}\u{201D} isn't a digraph.",
+                argument
+            ))),
+        }
+    }
+
+    fn preview(&self, argument: Option<&str>) -> Option<String> {
+        let text = self.digraphs.get(argument?)?;
+        Some(format!("Insert \u{201C}{}\u{201D}.", text))
+    }
+
+    fn suggest_arguments(
+        &self,
+        partial_argument: &str,
+        max_results: usize,
+    ) -> Vec<ArgumentSuggestion> {
+        if partial_argument.is_empty() {
+            return vec![];
+        }
+        self.digraphs
+            .find_by_prefix(partial_argument, max_results)
+            .into_iter()
+            .map(|(digraph, text)| ArgumentSuggestion {
+                argument: digraph.to_string(),
+                matches: vec![0..partial_argument.len()],
+                label: Some(text.to_string()),
+            })
+            .collect()
+    }
+
+    fn suggests_characters(&self) -> bool {
+        true
+    }
+}
+
+/// Describes each code point in the current selection, e.g. its official
+/// name and encodings, which is handy for figuring out what an odd character
+/// is. Text can also be given as an argument, e.g. `describe character ☃`.
+#[derive(Clone)]
+struct DescribeCommand {
+    names: CharacterNames,
+}
+
+impl Command for DescribeCommand {
+    fn name(&self) -> String {
+        String::from("describe character")
+    }
+
+    fn execute(&mut self, ui: &mut UserInterface) -> Result<CommandOutput, Error> {
+        // Copying the selection can take a moment, so it's done in a task.
+        let context = ui.context().clone();
+        let names = self.names.clone();
+        ui.spawn_task(&self.name(), move |_ctx| match context.selection() {
+            Some(text) if !text.is_empty() => {
+                Ok(CommandOutput::ShowMessage(describe_text(text, &names)))
+            }
+            _ => Err(Error::new(
+                "Please select the character you'd like described first.",
+            )),
+        })?;
+        Ok(CommandOutput::Nothing)
+    }
+
+    fn takes_argument(&self) -> bool {
+        true
+    }
+
+    fn execute_with_argument(
+        &mut self,
+        _ui: &mut UserInterface,
+        argument: &str,
+    ) -> Result<CommandOutput, Error> {
+        Ok(CommandOutput::ShowMessage(describe_text(
+            argument,
+            &self.names,
+        )))
+    }
+}
+
+/// Inserts the given text, first asking which skin tone to put it in if the
+/// user wants to be asked and it comes in several.
+fn insert_in_skin_tone(
+    text: &str,
+    skin_tone: SkinTonePreference,
+    usage: &SharedUsage,
+) -> CommandOutput {
+    if skin_tone == SkinTonePreference::Ask {
+        if let Some(variants) = get_skin_tone_variants(text) {
+            // The default is listed first, so that just tapping the
+            // quasimode key inserts the emoji as-is.
+            let mut choices = vec![insert_choice("default", text, usage)];
+            for (name, variant) in variants {
+                choices.push(insert_choice(name, variant, usage));
+            }
+            return CommandOutput::Prompt(Prompt::choose(
+                "Which skin tone would you like?",
+                choices,
+            ));
+        }
+    }
+    record_usage(usage, text);
+    CommandOutput::InsertText(text.to_string())
+}
+
+/// Returns a prompt choice for the given gender, e.g. `woman 🏃‍♀️`, which
+/// goes on to ask about the skin tone if need be.
+fn gender_choice(
+    name: &str,
+    text: &str,
+    skin_tone: SkinTonePreference,
+    usage: &SharedUsage,
+) -> Box<dyn Command + Send> {
+    let text = text.to_string();
+    let usage = usage.clone();
+    SimpleCommand::new(format!("{} {}", name, text), move |_ui| {
+        Ok(insert_in_skin_tone(&text, skin_tone, &usage))
+    })
+    .into_box()
+}
+
+/// Returns a prompt choice that inserts the given text, e.g. `medium 👋🏽`.
+fn insert_choice(name: &str, text: &str, usage: &SharedUsage) -> Box<dyn Command + Send> {
+    let text = text.to_string();
+    let usage = usage.clone();
+    SimpleCommand::new(format!("{} {}", name, text), move |_ui| {
+        record_usage(&usage, &text);
+        Ok(CommandOutput::InsertText(text.clone()))
+    })
+    .into_box()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::CommandContext;
+    use crate::command_registry::CommandRegistry;
+    use crate::pipeline::autocomplete;
+    use crate::windows_util::edit_input;
+    use winapi::um::winuser;
+
+    /// Types the given text the way the keyboard hook reports it, as virtual
+    /// key codes along with whether shift is down.
+    fn type_text(text: &str) -> String {
+        let mut input = String::new();
+        for ch in text.chars() {
+            let (vk_code, shift) = match ch {
+                'a'..='z' | '0'..='9' => (ch.to_ascii_uppercase() as i32, false),
+                'A'..='Z' => (ch as i32, true),
+                ' ' => (winuser::VK_SPACE, false),
+                ':' => (winuser::VK_OEM_1, true),
+                '^' => ('6' as i32, true),
+                '_' => (winuser::VK_OEM_MINUS, true),
+                '\\' => (winuser::VK_OEM_5, false),
+                _ => panic!("Don't know how to type {:?}", ch),
+            };
+            assert!(edit_input(&mut input, vk_code, shift));
+        }
+        input
+    }
+
+    /// Returns the preview of the menu item for exactly what was typed.
+    fn preview_typed(commands: &CommandRegistry, text: &str) -> Option<String> {
+        let input = type_text(text);
+        assert_eq!(input, text);
+        autocomplete(commands, &CommandContext::default(), &input, 10)
+            .into_iter()
+            .find(|sugg| sugg.name == input)
+            .and_then(|sugg| sugg.value.preview())
+    }
+
+    #[test]
+    fn test_typed_digraphs_keep_their_case() {
+        let mut commands = CommandRegistry::new();
+        let digraphs = Digraphs::builtin();
+        let usage = Arc::new(Mutex::new(CharacterUsage::default()));
+        commands.add(None, Box::new(InsertDigraphCommand { digraphs, usage }));
+        assert_eq!(
+            preview_typed(&commands, "insert digraph a:").as_deref(),
+            Some("Insert \u{201C}\u{E4}\u{201D}.")
+        );
+        assert_eq!(
+            preview_typed(&commands, "insert digraph A:").as_deref(),
+            Some("Insert \u{201C}\u{C4}\u{201D}.")
+        );
+        assert_eq!(
+            preview_typed(&commands, "insert digraph NS").as_deref(),
+            Some("Insert \u{201C}\u{A0}\u{201D}.")
+        );
+    }
+
+    #[test]
+    fn test_typed_latex_keeps_its_case() {
+        let mut commands = CommandRegistry::new();
+        let usage: SharedUsage = Arc::new(Mutex::new(CharacterUsage::default()));
+        commands.add(
+            None,
+            Box::new(TexCommand {
+                usage: usage.clone(),
+            }),
+        );
+        commands.add(
+            None,
+            Box::new(InsertCommand {
+                names: CharacterNames::default(),
+                skin_tone: SkinTonePreference::Default,
+                gender: GenderPreference::Default,
+                usage,
+            }),
+        );
+        for (text, expected) in [
+            ("tex \\rightarrow", "\u{2192}"),
+            ("tex \\Rightarrow", "\u{21D2}"),
+            ("tex \\Delta", "\u{394}"),
+            ("tex H_2O", "H\u{2082}O"),
+            ("tex x^A", "x\u{1D2C}"),
+            ("insert \\rightarrow", "\u{2192}"),
+            ("insert \\Rightarrow", "\u{21D2}"),
+        ] {
+            assert_eq!(
+                preview_typed(&commands, text),
+                Some(format!("Insert \u{201C}{}\u{201D}.", expected)),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_used_emoji_in_a_skin_tone_can_be_inserted_again() {
+        let mut usage = CharacterUsage::default();
+        usage.record("\u{1F44B}\u{1F3FD}").unwrap();
+        let command = InsertCommand {
+            names: CharacterNames::default(),
+            skin_tone: SkinTonePreference::Ask,
+            gender: GenderPreference::Ask,
+            usage: Arc::new(Mutex::new(usage)),
+        };
+        let sugg = command.suggest_arguments("waving", 10).remove(0);
+        assert_eq!(sugg.argument, "waving hand: medium skin tone");
+        assert_eq!(sugg.label.as_deref(), Some("\u{1F44B}\u{1F3FD}"));
+        assert_eq!(
+            command.preview(Some(&sugg.argument)).as_deref(),
+            Some("Insert \u{201C}\u{1F44B}\u{1F3FD}\u{201D}.")
+        );
     }
 }
@@ -0,0 +1,200 @@
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use serde_json::{json, Value};
+
+use crate::error::Error;
+use crate::system::get_enso_home_dir;
+
+const USAGE_FILENAME: &str = "character_usage.json";
+
+/// The most characters we remember having inserted. Pinned characters don't
+/// count towards this.
+const MAX_RECENT_CHARACTERS: usize = 50;
+
+/// Usage shared between the commands that insert characters and the ones that
+/// pin them.
+pub type SharedUsage = Arc<Mutex<CharacterUsage>>;
+
+/// Which characters the user has inserted and pinned, which lives in
+/// `~/.enso/character_usage.json`, so they can be suggested first.
+#[derive(Debug, Default)]
+pub struct CharacterUsage {
+    path: Option<PathBuf>,
+    pinned: Vec<String>,
+    /// Each character along with how many times it's been inserted, most
+    /// recently inserted first.
+    recent: Vec<(String, u32)>,
+}
+
+impl CharacterUsage {
+    /// Loads the usage file, if there is one.
+    pub fn load() -> Result<Self, Error> {
+        let mut path = get_enso_home_dir()?;
+        path.push(USAGE_FILENAME);
+        let mut usage = if path.exists() {
+            println!("Loading character usage from \"{}\".", path.display());
+            CharacterUsage::parse(&std::fs::read_to_string(&path)?)?
+        } else {
+            CharacterUsage::default()
+        };
+        usage.path = Some(path);
+        Ok(usage)
+    }
+
+    fn parse(text: &str) -> Result<Self, Error> {
+        let data: Value = serde_json::from_str(text).map_err(|err| Error::new(err.to_string()))?;
+        let pinned = data["pinned"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(String::from)
+            .collect();
+        let recent = data["recent"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|item| {
+                let text = item["text"].as_str()?;
+                let count = item["count"].as_u64().unwrap_or(1);
+                Some((text.to_string(), u32::try_from(count).unwrap_or(u32::MAX)))
+            })
+            .collect();
+        Ok(CharacterUsage {
+            path: None,
+            pinned,
+            recent,
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        let recent: Vec<Value> = self
+            .recent
+            .iter()
+            .map(|(text, count)| json!({"text": text, "count": count}))
+            .collect();
+        json!({"pinned": self.pinned, "recent": recent})
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        if let Some(path) = &self.path {
+            let text = serde_json::to_string_pretty(&self.to_json())
+                .map_err(|err| Error::new(err.to_string()))?;
+            std::fs::write(path, text)?;
+        }
+        Ok(())
+    }
+
+    /// Notes that the given text was just inserted.
+    pub fn record(&mut self, text: &str) -> Result<(), Error> {
+        let count = match self.recent.iter().position(|(other, _)| other == text) {
+            Some(idx) => self.recent.remove(idx).1.saturating_add(1),
+            None => 1,
+        };
+        self.recent.insert(0, (text.to_string(), count));
+        self.recent.truncate(MAX_RECENT_CHARACTERS);
+        self.save()
+    }
+
+    pub fn is_pinned(&self, text: &str) -> bool {
+        self.pinned.iter().any(|other| other == text)
+    }
+
+    /// Pins the given text, returning whether it wasn't already pinned.
+    pub fn pin(&mut self, text: &str) -> Result<bool, Error> {
+        if self.is_pinned(text) {
+            return Ok(false);
+        }
+        self.pinned.push(text.to_string());
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Unpins the given text, returning whether it was pinned.
+    pub fn unpin(&mut self, text: &str) -> Result<bool, Error> {
+        if !self.is_pinned(text) {
+            return Ok(false);
+        }
+        self.pinned.retain(|other| other != text);
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Returns whether the given text has been pinned or recently inserted.
+    pub fn is_used(&self, text: &str) -> bool {
+        self.is_pinned(text) || self.recent.iter().any(|(other, _)| other == text)
+    }
+
+    pub fn pinned(&self) -> &[String] {
+        &self.pinned
+    }
+
+    /// Returns the pinned characters in the order they were pinned, followed
+    /// by the others most recently inserted first.
+    pub fn recent(&self) -> Vec<&str> {
+        let mut found: Vec<&str> = self.pinned.iter().map(String::as_str).collect();
+        for (text, _) in &self.recent {
+            if !self.is_pinned(text) {
+                found.push(text);
+            }
+        }
+        found
+    }
+
+    /// Like `recent()`, but with the most often inserted characters first.
+    pub fn ranked(&self) -> Vec<&str> {
+        let mut recent: Vec<&(String, u32)> = self
+            .recent
+            .iter()
+            .filter(|(text, _)| !self.is_pinned(text))
+            .collect();
+        // This is stable, so characters inserted equally often stay in the
+        // order they were last inserted.
+        recent.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        let mut found: Vec<&str> = self.pinned.iter().map(String::as_str).collect();
+        found.extend(recent.into_iter().map(|(text, _)| text.as_str()));
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_works() {
+        let mut usage = CharacterUsage::default();
+        for text in ["\u{3B1}", "\u{3B1}", "\u{2603}", "\u{2192}"] {
+            usage.record(text).unwrap();
+        }
+        assert_eq!(usage.recent(), vec!["\u{2192}", "\u{2603}", "\u{3B1}"]);
+        assert_eq!(usage.ranked(), vec!["\u{3B1}", "\u{2192}", "\u{2603}"]);
+        assert!(usage.is_used("\u{2603}"));
+        assert!(!usage.is_used("\u{2604}"));
+    }
+
+    #[test]
+    fn test_pinned_characters_come_first() {
+        let mut usage = CharacterUsage::default();
+        usage.record("\u{3B1}").unwrap();
+        usage.record("\u{2603}").unwrap();
+        assert!(usage.pin("\u{3B1}").unwrap());
+        assert!(!usage.pin("\u{3B1}").unwrap());
+        assert_eq!(usage.recent(), vec!["\u{3B1}", "\u{2603}"]);
+        assert!(usage.unpin("\u{3B1}").unwrap());
+        assert_eq!(usage.recent(), vec!["\u{2603}", "\u{3B1}"]);
+    }
+
+    #[test]
+    fn test_parse_reads_what_was_saved() {
+        let mut usage = CharacterUsage::default();
+        usage.record("\u{2603}").unwrap();
+        usage.record("\u{2603}").unwrap();
+        usage.pin("\u{1F44B}\u{1F3FD}").unwrap();
+        let parsed = CharacterUsage::parse(&usage.to_json().to_string()).unwrap();
+        assert_eq!(parsed.pinned(), ["\u{1F44B}\u{1F3FD}"]);
+        assert_eq!(parsed.recent, vec![(String::from("\u{2603}"), 2)]);
+    }
+}
@@ -3,7 +3,7 @@ use std::path::PathBuf;
 use std::thread::sleep;
 use std::time::{Duration, SystemTime};
 
-use crate::command::SimpleCommand;
+use crate::command::{Command, CommandContext, CommandOutput};
 use crate::error::Error;
 use crate::system::{
     get_enso_home_dir, get_foreground_executable_path, get_foreground_window_name, press_key,
@@ -50,7 +50,6 @@ impl TryFrom<&str> for HotkeyCombination {
 #[derive(Default)]
 pub struct InvokeHotkeysPlugin {
     commands_loaded: Vec<String>,
-    last_foreground_executable_path: Option<String>,
     last_parse: Option<(SystemTime, HotkeyParseResult)>,
 }
 
@@ -139,7 +138,9 @@ impl InvokeHotkeysPlugin {
                     Ok(hotkey) => {
                         let command = HotkeyCommand {
                             name: format!("{} ({})", command_name.trim(), hotkey_str),
+                            hotkey_str: hotkey_str.to_string(),
                             hotkey,
+                            exe_filters: vec![],
                         };
                         current_section.commands.push(command);
                     }
@@ -175,27 +176,25 @@ impl InvokeHotkeysPlugin {
         let Some((_, parse_result)) = self.last_parse.as_ref() else {
             return Ok(());
         };
+        // The same hotkey can be listed under several apps, in which case it
+        // becomes a single command that's available in all of them.
+        let mut commands: Vec<HotkeyCommand> = vec![];
         for section in &parse_result.sections {
             let _ = section.name; // TODO: We should use this in the help text.
-            if let Some((exe_filter, exe_path)) = section
-                .exe_filter
-                .as_ref()
-                .zip(self.last_foreground_executable_path.as_ref())
-            {
-                if !exe_path.contains(exe_filter) {
-                    continue;
+            for mut command in section.commands.clone() {
+                command.exe_filters.push(section.exe_filter.clone());
+                match commands.iter_mut().find(|c| c.name == command.name) {
+                    Some(existing) => existing.exe_filters.append(&mut command.exe_filters),
+                    None => commands.push(command),
                 }
             }
-            for command in section.commands.clone() {
-                let hotkey = command.hotkey;
-                if ui.has_command(&command.name) {
-                    println!("Command \"{}\" already exists, skipping.", command.name);
-                } else {
-                    self.commands_loaded.push(command.name.clone());
-                    let simple_command =
-                        SimpleCommand::new(command.name, move |_ui| hotkey.press());
-                    ui.add_command(simple_command.into_box());
-                }
+        }
+        for command in commands {
+            if ui.has_command(&command.name) {
+                println!("Command \"{}\" already exists, skipping.", command.name);
+            } else {
+                self.commands_loaded.push(command.name.clone());
+                ui.add_command(Box::new(command));
             }
         }
         println!("Loaded {} hotkey commands.", self.commands_loaded.len());
@@ -203,21 +202,11 @@ impl InvokeHotkeysPlugin {
     }
 
     pub fn maybe_reload(&mut self, ui: &mut UserInterface) -> Result<(), Error> {
-        let foreground_executable_path: Option<String> = get_foreground_executable_path().ok();
-        let did_foreground_executable_change =
-            foreground_executable_path != self.last_foreground_executable_path;
-        let was_reparsed = self.maybe_reparse()?;
-
-        if !did_foreground_executable_change && !was_reparsed {
-            Ok(())
-        } else {
-            if did_foreground_executable_change {
-                self.last_foreground_executable_path = foreground_executable_path;
-            }
-            if was_reparsed {
-                self.show_parse_warnings(ui)?;
-            }
+        if self.maybe_reparse()? {
+            self.show_parse_warnings(ui)?;
             self.reload(ui)
+        } else {
+            Ok(())
         }
     }
 
@@ -253,16 +242,19 @@ impl InvokeHotkeysPlugin {
 }
 
 impl UserInterfacePlugin for InvokeHotkeysPlugin {
+    fn name(&self) -> String {
+        String::from("hotkeys")
+    }
+
     fn init(&mut self, ui: &mut UserInterface) -> Result<(), Error> {
         self.maybe_reload(ui)?;
-        ui.add_simple_command("show foreground window info", |ui| {
+        ui.add_simple_command("show foreground window info", |_ui| {
             let window_name = get_foreground_window_name().unwrap_or(String::from("ERR"));
             let executable_path = get_foreground_executable_path().unwrap_or(String::from("ERR"));
-            ui.show_message(format!(
+            Ok(CommandOutput::ShowMessage(format!(
                 "Window name: {}\nExecutable path: {}",
                 window_name, executable_path
-            ))?;
-            Ok(())
+            )))
         });
         Ok(())
     }
@@ -282,7 +274,41 @@ struct HotkeySection {
 #[derive(Clone, Debug)]
 struct HotkeyCommand {
     name: String,
+    hotkey_str: String,
     hotkey: HotkeyCombination,
+    /// The exe filters of every section this hotkey was listed in, where
+    /// `None` means it was listed globally.
+    exe_filters: Vec<Option<String>>,
+}
+
+impl Command for HotkeyCommand {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn execute(&mut self, ui: &mut UserInterface) -> Result<CommandOutput, Error> {
+        // Pressing the keys takes a while, since we pause between them, so
+        // it's done on a worker thread to keep from holding up the event loop.
+        let hotkey = self.hotkey.clone();
+        ui.spawn_task(&self.name, move |_ctx| hotkey.press())?;
+        Ok(CommandOutput::Nothing)
+    }
+
+    fn is_available(&self, context: &CommandContext) -> bool {
+        // If we can't tell what the foreground app is, err on the side of
+        // offering everything.
+        let Some(exe_path) = context.foreground_executable_path() else {
+            return true;
+        };
+        self.exe_filters.iter().any(|exe_filter| match exe_filter {
+            Some(exe_filter) => exe_path.contains(exe_filter.as_str()),
+            None => true,
+        })
+    }
+
+    fn preview(&self, _argument: Option<&str>) -> Option<String> {
+        Some(format!("Press {}.", self.hotkey_str.to_uppercase()))
+    }
 }
 
 #[derive(Clone)]
@@ -290,3 +316,23 @@ struct HotkeyParseResult {
     warnings: Vec<String>,
     sections: Vec<HotkeySection>,
 }
+
+#[test]
+fn test_hotkey_command_availability_works() {
+    let command = HotkeyCommand {
+        name: String::from("save (ctrl+s)"),
+        hotkey_str: String::from("ctrl+s"),
+        hotkey: HotkeyCombination { keys: vec![] },
+        exe_filters: vec![Some(String::from("notepad.exe"))],
+    };
+    let context = |exe: &str| CommandContext::new(Some(exe.to_string()), None, None);
+    assert!(command.is_available(&context("C:\\Windows\\notepad.exe")));
+    assert!(!command.is_available(&context("C:\\emacs.exe")));
+    assert!(command.is_available(&CommandContext::default()));
+
+    let global_command = HotkeyCommand {
+        exe_filters: vec![Some(String::from("notepad.exe")), None],
+        ..command
+    };
+    assert!(global_command.is_available(&context("C:\\emacs.exe")));
+}
@@ -0,0 +1,308 @@
+//! Lets users write their own commands as [Rhai][] scripts in
+//! `~/.enso/scripts/`. Scripts are reloaded whenever they change.
+//!
+//! Scripts can call the following functions:
+//!
+//! * `add_command(name, callback)` adds a command that calls the given
+//!   function (or closure) when it's run.
+//! * `show_message(text)` shows a message once the script or command is done.
+//! * `type_char(text)` types the given text into the current application.
+//! * `press_key(name)` presses and releases the given key, e.g. `"ctrl"` or
+//!   `"f5"`. `press_key(name, "down")` and `press_key(name, "up")` press or
+//!   release it on its own.
+//! * `foreground_window_name()` and `foreground_executable_path()` return
+//!   information about the current application.
+//!
+//! For example:
+//!
+//! ```text
+//! add_command("say hi", || show_message("Hi from " + foreground_window_name()));
+//! ```
+//!
+//! [Rhai]: https://rhai.rs/
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use rhai::{Dynamic, Engine, EvalAltResult, FnPtr, AST};
+
+use crate::command::{Command, CommandOutput};
+use crate::error::Error;
+use crate::system::{
+    get_enso_home_dir, get_foreground_executable_path, get_foreground_window_name, press_key,
+    type_char, KeyDirection, VirtualKey,
+};
+use crate::ui::{UserInterface, UserInterfacePlugin};
+
+const SCRIPT_EXTENSION: &str = "rhai";
+
+// This keeps a runaway script, e.g. one with an infinite loop, from freezing Enso.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// State shared between the scripting engine and the plugin.
+#[derive(Default)]
+struct ScriptState {
+    /// Commands added by the script that's currently being loaded.
+    new_commands: Vec<(String, FnPtr)>,
+    /// Messages shown by whatever script code is currently running.
+    messages: Vec<String>,
+}
+
+type SharedState = Rc<RefCell<ScriptState>>;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+fn to_script_result<T>(result: Result<T, Error>) -> ScriptResult<T> {
+    result.map_err(|err| err.to_string().into())
+}
+
+fn parse_key(name: &str) -> ScriptResult<VirtualKey> {
+    to_script_result(VirtualKey::try_from(name))
+}
+
+fn make_engine(state: &SharedState) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+
+    let add_command_state = state.clone();
+    engine.register_fn("add_command", move |name: &str, callback: FnPtr| {
+        add_command_state
+            .borrow_mut()
+            .new_commands
+            .push((name.to_string(), callback));
+    });
+    let show_message_state = state.clone();
+    engine.register_fn("show_message", move |text: &str| {
+        show_message_state
+            .borrow_mut()
+            .messages
+            .push(text.to_string());
+    });
+    engine.register_fn("type_char", |text: &str| to_script_result(type_char(text)));
+    engine.register_fn("press_key", |name: &str| -> ScriptResult<()> {
+        let key = parse_key(name)?;
+        to_script_result(press_key(key, KeyDirection::Down))?;
+        to_script_result(press_key(key, KeyDirection::Up))
+    });
+    engine.register_fn(
+        "press_key",
+        |name: &str, direction: &str| -> ScriptResult<()> {
+            let direction = match direction {
+                "down" => KeyDirection::Down,
+                "up" => KeyDirection::Up,
+                _ => return Err(format!("Invalid key direction \"{}\"", direction).into()),
+            };
+            to_script_result(press_key(parse_key(name)?, direction))
+        },
+    );
+    engine.register_fn("foreground_window_name", || {
+        get_foreground_window_name().unwrap_or_default()
+    });
+    engine.register_fn("foreground_executable_path", || {
+        get_foreground_executable_path().unwrap_or_default()
+    });
+    engine
+}
+
+struct LoadedScript {
+    last_modified: SystemTime,
+    commands: Vec<String>,
+}
+
+pub struct ScriptsPlugin {
+    engine: Rc<Engine>,
+    state: SharedState,
+    scripts: HashMap<PathBuf, LoadedScript>,
+}
+
+impl Default for ScriptsPlugin {
+    fn default() -> Self {
+        let state = SharedState::default();
+        ScriptsPlugin {
+            engine: Rc::new(make_engine(&state)),
+            state,
+            scripts: HashMap::new(),
+        }
+    }
+}
+
+impl ScriptsPlugin {
+    fn find_scripts(&self) -> Result<Vec<(PathBuf, SystemTime)>, Error> {
+        let mut scripts_dir = get_enso_home_dir()?;
+        scripts_dir.push("scripts");
+        let mut scripts = vec![];
+        if !scripts_dir.exists() {
+            return Ok(scripts);
+        }
+        for entry in std::fs::read_dir(scripts_dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) == Some(SCRIPT_EXTENSION) {
+                let last_modified = std::fs::metadata(&path)?.modified()?;
+                scripts.push((path, last_modified));
+            }
+        }
+        Ok(scripts)
+    }
+
+    fn unload(&mut self, ui: &mut UserInterface, path: &Path) {
+        if let Some(script) = self.scripts.remove(path) {
+            println!("Unloading script \"{}\".", path.display());
+            for command in script.commands.iter() {
+                ui.remove_command(command);
+            }
+        }
+    }
+
+    /// Runs the given script, returning the names of the commands it added.
+    fn load(&mut self, ui: &mut UserInterface, path: &Path) -> Result<Vec<String>, Error> {
+        println!("Loading script \"{}\".", path.display());
+        let ast = self
+            .engine
+            .compile_file(path.to_path_buf())
+            .map_err(|err| Error::new(err.to_string()))?;
+        let result = self.engine.run_ast(&ast);
+        let state = std::mem::take(&mut *self.state.borrow_mut());
+        result.map_err(|err| Error::new(err.to_string()))?;
+        if !state.messages.is_empty() {
+            ui.show_message(state.messages.join("\n"))?;
+        }
+        let ast = Rc::new(ast);
+        let mut commands = vec![];
+        for (name, callback) in state.new_commands {
+            if ui.has_command(&name) {
+                println!("Command \"{}\" already exists, skipping.", name);
+                continue;
+            }
+            commands.push(name.clone());
+            ui.add_command(Box::new(ScriptCommand {
+                name,
+                callback,
+                engine: self.engine.clone(),
+                ast: ast.clone(),
+                state: self.state.clone(),
+            }));
+        }
+        Ok(commands)
+    }
+
+    pub fn maybe_reload(&mut self, ui: &mut UserInterface) -> Result<(), Error> {
+        let scripts = self.find_scripts()?;
+        let removed: Vec<PathBuf> = self
+            .scripts
+            .keys()
+            .filter(|path| !scripts.iter().any(|(other, _)| other == *path))
+            .cloned()
+            .collect();
+        for path in removed {
+            self.unload(ui, &path);
+        }
+        let mut errors = vec![];
+        for (path, last_modified) in scripts {
+            if let Some(script) = self.scripts.get(&path) {
+                if script.last_modified == last_modified {
+                    continue;
+                }
+            }
+            self.unload(ui, &path);
+            // Even if the script failed to load, remember when it was last
+            // modified, so we don't complain about it again until it changes.
+            let commands = self.load(ui, &path).unwrap_or_else(|err| {
+                errors.push(format!("{}: {}", path.display(), err));
+                vec![]
+            });
+            let script = LoadedScript {
+                last_modified,
+                commands,
+            };
+            self.scripts.insert(path, script);
+        }
+        if !errors.is_empty() {
+            let message = format!(
+                "Problems occurred while loading scripts:\n{}",
+                errors.join("\n")
+            );
+            eprintln!("{}", message);
+            ui.show_message(message)?;
+        }
+        Ok(())
+    }
+}
+
+impl UserInterfacePlugin for ScriptsPlugin {
+    fn name(&self) -> String {
+        String::from("scripts")
+    }
+
+    fn init(&mut self, ui: &mut UserInterface) -> Result<(), Error> {
+        self.maybe_reload(ui)
+    }
+
+    fn on_quasimode_start(&mut self, ui: &mut UserInterface) -> Result<(), Error> {
+        self.maybe_reload(ui)
+    }
+}
+
+#[derive(Clone)]
+struct ScriptCommand {
+    name: String,
+    callback: FnPtr,
+    engine: Rc<Engine>,
+    ast: Rc<AST>,
+    state: SharedState,
+}
+
+impl Command for ScriptCommand {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn execute(&mut self, _ui: &mut UserInterface) -> Result<CommandOutput, Error> {
+        // We don't care what the callback returns, as long as it didn't fail.
+        let result = self.callback.call::<Dynamic>(&self.engine, &self.ast, ());
+        let state = std::mem::take(&mut *self.state.borrow_mut());
+        if let Err(err) = result {
+            return Err(Error::new(err.to_string()));
+        }
+        if state.messages.is_empty() {
+            Ok(CommandOutput::Nothing)
+        } else {
+            Ok(CommandOutput::ShowMessage(state.messages.join("\n")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(state: &SharedState, script: &str) -> ScriptResult<()> {
+        make_engine(state).run(script)
+    }
+
+    #[test]
+    fn test_scripts_can_add_commands_and_show_messages() {
+        let state = SharedState::default();
+        run(&state, r#"show_message("hi"); add_command("boop", || 1);"#).unwrap();
+        let state = state.borrow();
+        assert_eq!(state.messages, vec!["hi"]);
+        assert_eq!(state.new_commands.len(), 1);
+        assert_eq!(state.new_commands[0].0, "boop");
+    }
+
+    #[test]
+    fn test_runaway_scripts_are_stopped() {
+        let state = SharedState::default();
+        assert!(run(&state, "loop {}").is_err());
+    }
+
+    #[test]
+    fn test_invalid_key_directions_are_errors() {
+        let state = SharedState::default();
+        let err = run(&state, r#"press_key("ctrl", "sideways")"#).unwrap_err();
+        assert!(err.to_string().contains("Invalid key direction"));
+    }
+}
@@ -0,0 +1,616 @@
+//! Runs plugins compiled to WebAssembly from `~/.enso/plugins/*.wasm` in a
+//! sandbox, so they can be shared without trusting them with native code.
+//!
+//! A module must export its `memory` and an `enso_init()` function, which
+//! adds the plugin's commands by calling `add_command`. It can also export:
+//!
+//! * `enso_execute(index: i32) -> i32`, which runs the command with the given
+//!   index (in the order they were added), returning 0 on success.
+//! * `enso_preview(index: i32)`, which previews the command with the given
+//!   index by calling `set_preview`.
+//! * `enso_on_quasimode_start()`, `enso_on_quasimode_end()` and
+//!   `enso_on_shutdown()`, which are called at the corresponding times.
+//!
+//! Modules can import the following functions from the `enso` module. Strings
+//! are passed as a pointer and length in bytes, and must be UTF-8. Functions
+//! that give the plugin a string copy as much of it as fits into the given
+//! buffer and return its full length, or -1 if there's no string at all.
+//!
+//! * `add_command(name_ptr, name_len, flags)`, where the flags are
+//!   `1` if the command takes an argument and `2` if it has a preview.
+//! * `get_argument(buf_ptr, buf_len) -> i32` returns the argument of the
+//!   command that's being run or previewed.
+//! * `show_message(ptr, len)`, `set_preview(ptr, len)` and
+//!   `set_error(ptr, len)` set the message, preview or error message of the
+//!   command that's being run or previewed.
+//! * `insert_text(ptr, len)` inserts text into the current application. This
+//!   needs the `type_text` capability.
+//! * `read_clipboard(buf_ptr, buf_len) -> i32` returns the text on the
+//!   clipboard. This needs the `read_clipboard` capability.
+//! * `spawn_process(ptr, len)` starts a process, given its path and arguments
+//!   separated by newlines. This needs the `spawn_process` capability.
+//!
+//! Capabilities are granted by a manifest next to the module, with the same
+//! name but a `.json` extension, e.g. `{"capabilities": ["type_text"]}`.
+//!
+//! Each call into a module has a limited amount of fuel, so a misbehaving
+//! plugin can't freeze Enso.
+
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use serde_json::Value;
+use wasmi::{
+    Caller, Config, Engine, Instance, Linker, Module, Store, StoreLimits, StoreLimitsBuilder,
+    TrapCode, WasmParams, WasmResults,
+};
+
+use crate::command::{Command, CommandOutput};
+use crate::error::Error;
+use crate::plugins::get_plugin_name;
+use crate::system::{get_clipboard_text, get_enso_home_dir};
+use crate::ui::{PluginEntry, UserInterface, UserInterfacePlugin};
+
+const HOST_MODULE: &str = "enso";
+
+const TAKES_ARGUMENT_FLAG: i32 = 1;
+const HAS_PREVIEW_FLAG: i32 = 2;
+
+/// How much fuel each call into a module gets. One unit of fuel is roughly
+/// one instruction.
+const FUEL_PER_CALL: u64 = 10_000_000;
+
+const MAX_MEMORY_BYTES: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Capability {
+    TypeText,
+    ReadClipboard,
+    SpawnProcess,
+}
+
+impl Capability {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "type_text" => Some(Capability::TypeText),
+            "read_clipboard" => Some(Capability::ReadClipboard),
+            "spawn_process" => Some(Capability::SpawnProcess),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CommandInfo {
+    name: String,
+    takes_argument: bool,
+    has_preview: bool,
+}
+
+struct HostState {
+    capabilities: Vec<Capability>,
+    limits: StoreLimits,
+    new_commands: Vec<CommandInfo>,
+    argument: Option<String>,
+    output: CommandOutput,
+    preview: Option<String>,
+    error: Option<String>,
+}
+
+type HostResult<T> = Result<T, wasmi::Error>;
+
+fn read_string(caller: &Caller<'_, HostState>, ptr: i32, len: i32) -> HostResult<String> {
+    let memory = caller
+        .get_export("memory")
+        .and_then(|export| export.into_memory())
+        .ok_or_else(|| wasmi::Error::new("Module doesn't export its memory"))?;
+    let mut buffer = vec![0; len.max(0) as usize];
+    memory
+        .read(caller, ptr as u32 as usize, &mut buffer)
+        .map_err(|err| wasmi::Error::new(err.to_string()))?;
+    String::from_utf8(buffer).map_err(|err| wasmi::Error::new(err.to_string()))
+}
+
+/// Copies as much of the given string as will fit into the given buffer,
+/// returning its full length, or -1 if there's no string.
+fn write_string(
+    caller: &mut Caller<'_, HostState>,
+    buf_ptr: i32,
+    buf_len: i32,
+    string: Option<String>,
+) -> HostResult<i32> {
+    let Some(string) = string else {
+        return Ok(-1);
+    };
+    let memory = caller
+        .get_export("memory")
+        .and_then(|export| export.into_memory())
+        .ok_or_else(|| wasmi::Error::new("Module doesn't export its memory"))?;
+    let bytes = string.as_bytes();
+    let count = bytes.len().min(buf_len.max(0) as usize);
+    memory
+        .write(caller, buf_ptr as u32 as usize, &bytes[..count])
+        .map_err(|err| wasmi::Error::new(err.to_string()))?;
+    Ok(bytes.len() as i32)
+}
+
+fn require(caller: &Caller<'_, HostState>, capability: Capability) -> HostResult<()> {
+    if caller.data().capabilities.contains(&capability) {
+        Ok(())
+    } else {
+        Err(wasmi::Error::new(format!(
+            "The plugin doesn't have the {:?} capability.",
+            capability
+        )))
+    }
+}
+
+fn make_linker(engine: &Engine) -> HostResult<Linker<HostState>> {
+    let mut linker = Linker::new(engine);
+    linker.func_wrap(
+        HOST_MODULE,
+        "add_command",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32, flags: i32| -> HostResult<()> {
+            let name = read_string(&caller, ptr, len)?;
+            caller.data_mut().new_commands.push(CommandInfo {
+                name,
+                takes_argument: flags & TAKES_ARGUMENT_FLAG != 0,
+                has_preview: flags & HAS_PREVIEW_FLAG != 0,
+            });
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "get_argument",
+        |mut caller: Caller<'_, HostState>, buf_ptr: i32, buf_len: i32| -> HostResult<i32> {
+            let argument = caller.data().argument.clone();
+            write_string(&mut caller, buf_ptr, buf_len, argument)
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "show_message",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> HostResult<()> {
+            caller.data_mut().output = CommandOutput::ShowMessage(read_string(&caller, ptr, len)?);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "set_preview",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> HostResult<()> {
+            caller.data_mut().preview = Some(read_string(&caller, ptr, len)?);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "set_error",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> HostResult<()> {
+            caller.data_mut().error = Some(read_string(&caller, ptr, len)?);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "insert_text",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> HostResult<()> {
+            require(&caller, Capability::TypeText)?;
+            caller.data_mut().output = CommandOutput::InsertText(read_string(&caller, ptr, len)?);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "read_clipboard",
+        |mut caller: Caller<'_, HostState>, buf_ptr: i32, buf_len: i32| -> HostResult<i32> {
+            require(&caller, Capability::ReadClipboard)?;
+            let text = get_clipboard_text().map_err(|err| wasmi::Error::new(err.to_string()))?;
+            write_string(&mut caller, buf_ptr, buf_len, text)
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "spawn_process",
+        |caller: Caller<'_, HostState>, ptr: i32, len: i32| -> HostResult<()> {
+            require(&caller, Capability::SpawnProcess)?;
+            let command_line = read_string(&caller, ptr, len)?;
+            let mut parts = command_line.lines();
+            let program = parts
+                .next()
+                .ok_or_else(|| wasmi::Error::new("No program given"))?;
+            std::process::Command::new(program)
+                .args(parts)
+                .spawn()
+                .map_err(|err| wasmi::Error::new(err.to_string()))?;
+            Ok(())
+        },
+    )?;
+    Ok(linker)
+}
+
+/// An instantiated module, along with everything it's told us.
+struct WasmInstance {
+    name: String,
+    store: Store<HostState>,
+    instance: Instance,
+    commands: Vec<CommandInfo>,
+}
+
+impl WasmInstance {
+    fn new(name: &str, wasm: &[u8], capabilities: Vec<Capability>) -> Result<Self, Error> {
+        let to_error = |err: wasmi::Error| {
+            Error::new(format!(
+                "The \u{201C}{}\u{201D} plugin couldn't be loaded: {}",
+                name, err
+            ))
+        };
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, wasm).map_err(to_error)?;
+        let state = HostState {
+            capabilities,
+            limits: StoreLimitsBuilder::new()
+                .memory_size(MAX_MEMORY_BYTES)
+                .build(),
+            new_commands: vec![],
+            argument: None,
+            output: CommandOutput::Nothing,
+            preview: None,
+            error: None,
+        };
+        let mut store = Store::new(&engine, state);
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(FUEL_PER_CALL).map_err(to_error)?;
+        let linker = make_linker(&engine).map_err(to_error)?;
+        let instance = linker
+            .instantiate_and_start(&mut store, &module)
+            .map_err(to_error)?;
+        let mut result = WasmInstance {
+            name: name.to_string(),
+            store,
+            instance,
+            commands: vec![],
+        };
+        if result.call::<(), ()>("enso_init", ())?.is_none() {
+            return Err(result.error("It doesn't export \u{201C}enso_init\u{201D}."));
+        }
+        result.commands = std::mem::take(&mut result.store.data_mut().new_commands);
+        Ok(result)
+    }
+
+    fn load(path: &Path) -> Result<Self, Error> {
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let capabilities = read_capabilities(&path.with_extension("json"))?;
+        WasmInstance::new(&name, &std::fs::read(path)?, capabilities)
+    }
+
+    fn error<S: AsRef<str>>(&self, message: S) -> Error {
+        Error::new(format!(
+            "The \u{201C}{}\u{201D} plugin had a problem: {}",
+            self.name,
+            message.as_ref()
+        ))
+    }
+
+    /// Calls the given export with a fresh tank of fuel, returning `None` if
+    /// the module doesn't export it.
+    fn call<Params: WasmParams, Results: WasmResults>(
+        &mut self,
+        name: &str,
+        params: Params,
+    ) -> Result<Option<Results>, Error> {
+        let Ok(func) = self
+            .instance
+            .get_typed_func::<Params, Results>(&self.store, name)
+        else {
+            return Ok(None);
+        };
+        self.store
+            .set_fuel(FUEL_PER_CALL)
+            .map_err(|err| self.error(err.to_string()))?;
+        match func.call(&mut self.store, params) {
+            Ok(results) => Ok(Some(results)),
+            Err(err) if err.as_trap_code() == Some(TrapCode::OutOfFuel) => {
+                Err(self.error("It took too long, so it was stopped."))
+            }
+            Err(err) => Err(self.error(err.to_string())),
+        }
+    }
+
+    fn begin_command(&mut self, argument: Option<&str>) {
+        let state = self.store.data_mut();
+        state.argument = argument.map(String::from);
+        state.output = CommandOutput::Nothing;
+        state.preview = None;
+        state.error = None;
+    }
+
+    fn execute(&mut self, index: i32, argument: Option<&str>) -> Result<CommandOutput, Error> {
+        self.begin_command(argument);
+        let Some(status) = self.call::<i32, i32>("enso_execute", index)? else {
+            return Err(self.error("It doesn't export \u{201C}enso_execute\u{201D}."));
+        };
+        let state = self.store.data_mut();
+        let output = std::mem::replace(&mut state.output, CommandOutput::Nothing);
+        if status == 0 {
+            Ok(output)
+        } else {
+            let message = state
+                .error
+                .take()
+                .unwrap_or_else(|| format!("It failed with status {}.", status));
+            Err(self.error(message))
+        }
+    }
+
+    fn preview(&mut self, index: i32, argument: Option<&str>) -> Result<Option<String>, Error> {
+        self.begin_command(argument);
+        self.call::<i32, ()>("enso_preview", index)?;
+        Ok(self.store.data_mut().preview.take())
+    }
+}
+
+/// Reads the capabilities granted by the given manifest, if it exists.
+fn read_capabilities(manifest_path: &Path) -> Result<Vec<Capability>, Error> {
+    if !manifest_path.exists() {
+        return Ok(vec![]);
+    }
+    let manifest: Value = serde_json::from_str(&std::fs::read_to_string(manifest_path)?)
+        .map_err(|err| Error::new(err.to_string()))?;
+    let mut capabilities = vec![];
+    let names = manifest.get("capabilities").and_then(Value::as_array);
+    for name in names.into_iter().flatten() {
+        match name.as_str().and_then(Capability::parse) {
+            Some(capability) => capabilities.push(capability),
+            None => println!(
+                "Ignoring unknown capability {} in \"{}\".",
+                name,
+                manifest_path.display()
+            ),
+        }
+    }
+    Ok(capabilities)
+}
+
+/// Returns a plugin for every `.wasm` file in `~/.enso/plugins`.
+pub fn get_wasm_plugins() -> Vec<PluginEntry> {
+    let Ok(mut plugins_dir) = get_enso_home_dir() else {
+        return vec![];
+    };
+    plugins_dir.push("plugins");
+    let Ok(entries) = std::fs::read_dir(&plugins_dir) else {
+        return vec![];
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("wasm"))
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            PluginEntry::new(get_plugin_name(&path), move || {
+                Box::new(WasmPlugin::new(path.clone()))
+            })
+        })
+        .collect()
+}
+
+pub struct WasmPlugin {
+    path: PathBuf,
+    instance: Option<Rc<RefCell<WasmInstance>>>,
+}
+
+impl WasmPlugin {
+    pub fn new(path: PathBuf) -> Self {
+        WasmPlugin {
+            path,
+            instance: None,
+        }
+    }
+
+    fn call_hook(&mut self, name: &str) -> Result<(), Error> {
+        if let Some(instance) = &self.instance {
+            if let Ok(mut instance) = instance.try_borrow_mut() {
+                instance.call::<(), ()>(name, ())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl UserInterfacePlugin for WasmPlugin {
+    fn name(&self) -> String {
+        get_plugin_name(&self.path)
+    }
+
+    fn init(&mut self, ui: &mut UserInterface) -> Result<(), Error> {
+        println!("Loading WebAssembly plugin \"{}\".", self.path.display());
+        let instance = match WasmInstance::load(&self.path) {
+            Ok(instance) => Rc::new(RefCell::new(instance)),
+            Err(error) => {
+                // A broken plugin shouldn't stop Enso from starting.
+                return ui.show_message(format!(
+                    "An error occurred when loading a plugin:\n{}",
+                    error
+                ));
+            }
+        };
+        for (index, info) in instance.borrow().commands.iter().enumerate() {
+            if ui.has_command(&info.name) {
+                println!("Command \"{}\" already exists, skipping.", info.name);
+                continue;
+            }
+            ui.add_command(Box::new(WasmCommand {
+                info: info.clone(),
+                index: index as i32,
+                instance: instance.clone(),
+            }));
+        }
+        self.instance = Some(instance);
+        Ok(())
+    }
+
+    fn on_quasimode_start(&mut self, _ui: &mut UserInterface) -> Result<(), Error> {
+        self.call_hook("enso_on_quasimode_start")
+    }
+
+    fn on_quasimode_end(&mut self, _ui: &mut UserInterface) -> Result<(), Error> {
+        self.call_hook("enso_on_quasimode_end")
+    }
+
+    fn on_shutdown(&mut self, _ui: &mut UserInterface) -> Result<(), Error> {
+        self.call_hook("enso_on_shutdown")
+    }
+}
+
+#[derive(Clone)]
+struct WasmCommand {
+    info: CommandInfo,
+    index: i32,
+    instance: Rc<RefCell<WasmInstance>>,
+}
+
+impl WasmCommand {
+    fn run(&self, argument: Option<&str>) -> Result<CommandOutput, Error> {
+        self.instance
+            .try_borrow_mut()
+            .map_err(|_| Error::new("The plugin is busy."))?
+            .execute(self.index, argument)
+    }
+}
+
+impl Command for WasmCommand {
+    fn name(&self) -> String {
+        self.info.name.clone()
+    }
+
+    fn execute(&mut self, _ui: &mut UserInterface) -> Result<CommandOutput, Error> {
+        self.run(None)
+    }
+
+    fn takes_argument(&self) -> bool {
+        self.info.takes_argument
+    }
+
+    fn execute_with_argument(
+        &mut self,
+        _ui: &mut UserInterface,
+        argument: &str,
+    ) -> Result<CommandOutput, Error> {
+        self.run(Some(argument))
+    }
+
+    fn preview(&self, argument: Option<&str>) -> Option<String> {
+        if !self.info.has_preview {
+            return None;
+        }
+        let mut instance = self.instance.try_borrow_mut().ok()?;
+        match instance.preview(self.index, argument) {
+            Ok(preview) => preview,
+            Err(error) => {
+                eprintln!("{}", error);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Adds an "echo" command that shows its argument, and a "type" command
+    // that tries to insert some text.
+    const ECHO_WAT: &str = r#"
+        (module
+            (import "enso" "add_command" (func $add_command (param i32 i32 i32)))
+            (import "enso" "get_argument" (func $get_argument (param i32 i32) (result i32)))
+            (import "enso" "show_message" (func $show_message (param i32 i32)))
+            (import "enso" "set_preview" (func $set_preview (param i32 i32)))
+            (import "enso" "insert_text" (func $insert_text (param i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "echo")
+            (data (i32.const 4) "type")
+            (func (export "enso_init")
+                (call $add_command (i32.const 0) (i32.const 4) (i32.const 3))
+                (call $add_command (i32.const 4) (i32.const 4) (i32.const 0)))
+            (func (export "enso_execute") (param $index i32) (result i32)
+                (local $len i32)
+                (if (i32.eq (local.get $index) (i32.const 1))
+                    (then
+                        (call $insert_text (i32.const 4) (i32.const 4))
+                        (return (i32.const 0))))
+                (local.set $len (call $get_argument (i32.const 100) (i32.const 100)))
+                (if (i32.lt_s (local.get $len) (i32.const 0))
+                    (then (return (i32.const 1))))
+                (call $show_message (i32.const 100) (local.get $len))
+                (i32.const 0))
+            (func (export "enso_preview") (param $index i32)
+                (call $set_preview (i32.const 0) (i32.const 4))))
+    "#;
+
+    const LOOP_WAT: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "enso_init")
+                (loop $forever (br $forever))))
+    "#;
+
+    #[test]
+    fn test_commands_work() {
+        let mut instance = WasmInstance::new("echo", ECHO_WAT.as_bytes(), vec![]).unwrap();
+        let names: Vec<&str> = instance.commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["echo", "type"]);
+        assert!(instance.commands[0].takes_argument);
+        assert!(instance.commands[0].has_preview);
+        assert_eq!(
+            instance.execute(0, Some("hi")).unwrap(),
+            CommandOutput::ShowMessage(String::from("hi"))
+        );
+        assert!(instance.execute(0, None).is_err());
+        assert_eq!(
+            instance.preview(0, None).unwrap(),
+            Some(String::from("echo"))
+        );
+    }
+
+    #[test]
+    fn test_capabilities_are_enforced() {
+        let mut instance = WasmInstance::new("echo", ECHO_WAT.as_bytes(), vec![]).unwrap();
+        let err = instance.execute(1, None).unwrap_err();
+        assert!(err.to_string().contains("TypeText capability"));
+
+        let mut instance =
+            WasmInstance::new("echo", ECHO_WAT.as_bytes(), vec![Capability::TypeText]).unwrap();
+        assert_eq!(
+            instance.execute(1, None).unwrap(),
+            CommandOutput::InsertText(String::from("type"))
+        );
+    }
+
+    #[test]
+    fn test_runaway_plugins_are_stopped() {
+        let err = WasmInstance::new("loop", LOOP_WAT.as_bytes(), vec![])
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("took too long"));
+    }
+
+    #[test]
+    fn test_capability_parse_works() {
+        assert_eq!(
+            Capability::parse("read_clipboard"),
+            Some(Capability::ReadClipboard)
+        );
+        assert_eq!(Capability::parse("blarg"), None);
+    }
+}
@@ -0,0 +1,165 @@
+use std::fmt;
+
+use super::autocomplete_map::{AutocompleteMap, AutocompleteSuggestion};
+use super::command::{Command, CommandOutput, SimpleCommand};
+use super::error::Error;
+use super::pipeline::Pipeline;
+use super::ui::UserInterface;
+
+/// A follow-up question that a command can ask by returning it as output,
+/// e.g. to confirm something or to pick between several options.
+///
+/// The user answers it the next time they enter the quasimode, where the
+/// choices are shown in the menu instead of the usual commands. Each choice
+/// is itself a command, which is run if the user picks it.
+#[derive(Clone)]
+pub struct Prompt {
+    message: String,
+    choices: Vec<Box<dyn Command + Send>>,
+    /// The plugin that asked the question, if any, on whose behalf the
+    /// choices are run.
+    plugin: Option<String>,
+}
+
+impl Prompt {
+    pub fn choose<S: Into<String>>(message: S, choices: Vec<Box<dyn Command + Send>>) -> Self {
+        Prompt {
+            message: message.into(),
+            choices,
+            plugin: None,
+        }
+    }
+
+    /// Asks a yes/no question, calling the given callback if the answer is yes.
+    ///
+    /// "No" is listed first, so that just tapping the quasimode key dismisses
+    /// the question without doing anything.
+    pub fn confirm<S, F, R>(message: S, on_yes: F) -> Self
+    where
+        S: Into<String>,
+        F: FnMut(&mut UserInterface) -> Result<R, Error> + Clone + Send + 'static,
+        R: Into<CommandOutput> + 'static,
+    {
+        Self::choose(
+            message,
+            vec![
+                SimpleCommand::new("no", |_ui| Ok(CommandOutput::Nothing)).into_box(),
+                SimpleCommand::new("yes", on_yes).into_box(),
+            ],
+        )
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Records which plugin asked the question. The UI does this when the
+    /// prompt is returned, since plugins don't know their own names.
+    pub fn with_plugin(mut self, plugin: Option<String>) -> Self {
+        self.plugin = plugin;
+        self
+    }
+
+    /// Returns the choices matching the given input. If the input is empty,
+    /// all the choices are returned in their original order.
+    pub fn autocomplete(
+        &self,
+        input: &str,
+        max_results: usize,
+    ) -> Vec<AutocompleteSuggestion<Pipeline>> {
+        if input.is_empty() {
+            return self
+                .choices
+                .iter()
+                .map(|choice| AutocompleteSuggestion {
+                    name: choice.name(),
+                    matches: vec![],
+                    value: Pipeline::single(choice.clone(), self.plugin.clone()),
+                })
+                .collect();
+        }
+        let mut choices: AutocompleteMap<Box<dyn Command>> = AutocompleteMap::new();
+        for choice in self.choices.iter() {
+            choices.insert(choice.name(), choice.clone() as Box<dyn Command>);
+        }
+        choices
+            .autocomplete(input, max_results)
+            .into_iter()
+            .map(|sugg| AutocompleteSuggestion {
+                name: sugg.name,
+                matches: sugg.matches,
+                value: Pipeline::single(sugg.value, self.plugin.clone()),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Debug for Prompt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let choices: Vec<String> = self.choices.iter().map(|choice| choice.name()).collect();
+        f.debug_struct("Prompt")
+            .field("message", &self.message)
+            .field("choices", &choices)
+            .finish()
+    }
+}
+
+/// Prompts are equal if they ask the same thing with choices of the same
+/// names, since the choices themselves can't be compared.
+impl PartialEq for Prompt {
+    fn eq(&self, other: &Self) -> bool {
+        self.message == other.message
+            && self.choices.len() == other.choices.len()
+            && self
+                .choices
+                .iter()
+                .zip(other.choices.iter())
+                .all(|(choice, other_choice)| choice.name() == other_choice.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(suggs: Vec<AutocompleteSuggestion<Pipeline>>) -> Vec<String> {
+        suggs.into_iter().map(|sugg| sugg.name).collect()
+    }
+
+    #[test]
+    fn test_confirm_lists_no_first() {
+        let prompt = Prompt::confirm("Really?", |_ui| Ok(()));
+        assert_eq!(prompt.message(), "Really?");
+        assert_eq!(names(prompt.autocomplete("", 5)), vec!["no", "yes"]);
+    }
+
+    #[test]
+    fn test_autocomplete_filters_choices() {
+        let prompt = Prompt::confirm("Really?", |_ui| Ok(()));
+        assert_eq!(names(prompt.autocomplete("y", 5)), vec!["yes"]);
+        assert!(prompt.autocomplete("blarg", 5).is_empty());
+    }
+
+    #[test]
+    fn test_choices_belong_to_the_prompts_plugin() {
+        let output = CommandOutput::Prompt(Prompt::confirm("Really?", |_ui| Ok(())));
+        let CommandOutput::Prompt(prompt) = output.with_plugin(Some("boop".into())) else {
+            panic!("Expected a prompt");
+        };
+        for input in ["", "y"] {
+            for sugg in prompt.autocomplete(input, 5) {
+                assert_eq!(sugg.value.final_plugin(), Some(String::from("boop")));
+            }
+        }
+    }
+
+    #[test]
+    fn test_prompts_can_be_compared() {
+        let prompt = Prompt::confirm("Really?", |_ui| Ok(()));
+        assert_eq!(prompt, prompt.clone());
+        assert_ne!(prompt, Prompt::choose("Really?", vec![]));
+        assert_ne!(prompt, Prompt::confirm("Truly?", |_ui| Ok(())));
+        let yes = SimpleCommand::new("yes", |_ui| Ok(())).into_box();
+        assert_ne!(prompt, Prompt::choose("Really?", vec![yes.clone(), yes]));
+    }
+}
@@ -1,4 +1,13 @@
-use std::{convert::TryFrom, path::PathBuf, process::Command};
+use std::{
+    convert::TryFrom,
+    path::PathBuf,
+    process::Command,
+    sync::{Mutex, MutexGuard, PoisonError},
+    thread::{self, sleep},
+    time::Duration,
+};
+
+use unicode_segmentation::UnicodeSegmentation;
 
 /// This module is intened to provide an OS-independent way to access system functionality
 /// that platform-independent commands can use.
@@ -7,6 +16,29 @@ use std::{convert::TryFrom, path::PathBuf, process::Command};
 /// Windows currently.
 use crate::{error::Error, windows_util};
 
+// How long we'll wait for an application to copy its selection to the clipboard.
+const SELECTION_POLL_ATTEMPTS: u32 = 10;
+const SELECTION_POLL_INTERVAL_MS: u64 = 20;
+
+// How long we'll give an application to paste from the clipboard before we
+// put back what was there, unless the `paste_delay_ms` setting says otherwise.
+pub const DEFAULT_PASTE_DELAY_MS: u64 = 500;
+
+// The most UTF-16 code units we'll type in one go. Windows can drop input if
+// it's sent too much at once.
+const MAX_KEYSTROKE_BATCH: usize = 256;
+
+/// How text gets inserted into the foreground application.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InsertionMethod {
+    /// Simulate a Unicode keystroke for each UTF-16 code unit.
+    Type,
+    /// Put the text on the clipboard and simulate CTRL+V, for applications
+    /// that mangle simulated Unicode keystrokes. The clipboard is restored
+    /// once the application has had the given time to read it.
+    Paste(Duration),
+}
+
 #[derive(Debug)]
 pub enum KeyDirection {
     Up,
@@ -143,10 +175,135 @@ pub fn press_key(ch: VirtualKey, direction: KeyDirection) -> Result<(), Error> {
     windows_util::send_virtual_keypress(ch, direction)
 }
 
-/// Insert the given unicode character into the current application. This doesn't
+/// Splits the given text into batches of at most the given number of UTF-16
+/// code units, without splitting up any grapheme clusters, e.g. emoji or
+/// letters with combining marks. A grapheme cluster that's too big on its own
+/// gets a batch to itself.
+fn split_into_batches(text: &str, max_units: usize) -> Vec<&str> {
+    let mut batches = vec![];
+    let mut start = 0;
+    let mut units = 0;
+    for (idx, grapheme) in text.grapheme_indices(true) {
+        let grapheme_units = grapheme.encode_utf16().count();
+        if units > 0 && units + grapheme_units > max_units {
+            batches.push(&text[start..idx]);
+            start = idx;
+            units = 0;
+        }
+        units += grapheme_units;
+    }
+    if start < text.len() {
+        batches.push(&text[start..]);
+    }
+    batches
+}
+
+/// Insert the given unicode text into the current application. This doesn't
 /// take into account the current modifier keys or anything.
+///
+/// Surrogate pairs and grapheme clusters like emoji ZWJ sequences are always
+/// typed together, so nothing else can end up in the middle of them.
 pub fn type_char(ch: &str) -> Result<(), Error> {
-    windows_util::send_unicode_keypress(ch)
+    for batch in split_into_batches(ch, MAX_KEYSTROKE_BATCH) {
+        windows_util::send_unicode_keypress(batch)?;
+    }
+    Ok(())
+}
+
+/// Simulates CTRL plus the given key, e.g. CTRL+V. CTRL is released even if
+/// pressing the key fails, so that it doesn't get stuck down.
+fn press_ctrl_and(ch: char) -> Result<(), Error> {
+    let key = VirtualKey::Alphanumeric(AlphanumericKey::new(ch).unwrap());
+    press_key(VirtualKey::Control, KeyDirection::Down)?;
+    let pressed =
+        press_key(key, KeyDirection::Down).and_then(|()| press_key(key, KeyDirection::Up));
+    let released = press_key(VirtualKey::Control, KeyDirection::Up);
+    pressed.and(released)
+}
+
+/// What was on the clipboard before a paste, waiting to be put back.
+struct PendingRestore {
+    text: Option<String>,
+    /// The clipboard's sequence number right after the paste, so we can tell
+    /// whether anything has been copied since.
+    sequence_number: u32,
+}
+
+// If the user pastes again before the clipboard from the last paste has been
+// restored, what's on the clipboard is the last paste's text rather than
+// theirs, so the new paste has to take over restoring the old text.
+static PENDING_RESTORE: Mutex<Option<PendingRestore>> = Mutex::new(None);
+
+fn lock_pending_restore() -> MutexGuard<'static, Option<PendingRestore>> {
+    PENDING_RESTORE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Puts back what was on the clipboard before the paste with the given
+/// sequence number, unless a later paste has taken over or something else
+/// has been copied since.
+fn restore_clipboard(sequence_number: u32) -> Result<(), Error> {
+    let mut pending = lock_pending_restore();
+    let is_ours = matches!(&*pending, Some(restore) if restore.sequence_number == sequence_number);
+    if !is_ours {
+        return Ok(());
+    }
+    let text = pending.take().and_then(|restore| restore.text);
+    match text {
+        Some(text) if windows_util::get_clipboard_sequence_number() == sequence_number => {
+            set_clipboard_text(&text)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Insert the given text into the current application by pasting it from the
+/// clipboard. Any text that was on the clipboard beforehand is restored on a
+/// worker thread once the application has had the given time to read it, but
+/// other kinds of clipboard data are lost.
+///
+/// If something else is copied in the meantime, the clipboard is left alone,
+/// since that's what the user wants on it now.
+pub fn paste_text(text: &str, delay: Duration) -> Result<(), Error> {
+    let mut pending = lock_pending_restore();
+    let original_text = match pending.take() {
+        Some(restore)
+            if windows_util::get_clipboard_sequence_number() == restore.sequence_number =>
+        {
+            restore.text
+        }
+        _ => get_clipboard_text()?,
+    };
+    set_clipboard_text(text)?;
+    let sequence_number = windows_util::get_clipboard_sequence_number();
+    *pending = Some(PendingRestore {
+        text: original_text,
+        sequence_number,
+    });
+    drop(pending);
+    let pasted = press_ctrl_and('v');
+    if pasted.is_err() {
+        restore_clipboard(sequence_number)?;
+        return pasted;
+    }
+    thread::Builder::new()
+        .name(String::from("Clipboard restorer"))
+        .spawn(move || {
+            sleep(delay);
+            if let Err(error) = restore_clipboard(sequence_number) {
+                eprintln!("Unable to restore the clipboard: {}", error);
+            }
+        })?;
+    Ok(())
+}
+
+/// Insert the given text into the current application using the given method.
+pub fn insert_text(text: &str, method: InsertionMethod) -> Result<(), Error> {
+    match method {
+        InsertionMethod::Type => type_char(text),
+        InsertionMethod::Paste(delay) => paste_text(text, delay),
+    }
 }
 
 /// Returns Enso's home directory for the current user, usually found at
@@ -176,6 +333,50 @@ pub fn open_in_explorer(path: &PathBuf) -> Result<(), Error> {
     Ok(())
 }
 
+/// Returns the text currently on the clipboard, if any.
+pub fn get_clipboard_text() -> Result<Option<String>, Error> {
+    windows_util::get_clipboard_text()
+}
+
+pub fn set_clipboard_text(text: &str) -> Result<(), Error> {
+    windows_util::set_clipboard_text(text)
+}
+
+/// Returns the text currently selected in the foreground application, if any.
+///
+/// This works by simulating CTRL+C, so it temporarily replaces the contents of
+/// the clipboard, which are put back afterwards unless something else was
+/// copied in the meantime. The application can take a moment to copy its
+/// selection, so this shouldn't be called on the UI thread.
+pub fn get_selected_text() -> Result<Option<String>, Error> {
+    let original = windows_util::save_clipboard()?;
+    let sequence_number = windows_util::get_clipboard_sequence_number();
+    press_ctrl_and('c')?;
+    let mut copied = None;
+    for _ in 0..SELECTION_POLL_ATTEMPTS {
+        sleep(Duration::from_millis(SELECTION_POLL_INTERVAL_MS));
+        let current_sequence_number = windows_util::get_clipboard_sequence_number();
+        if current_sequence_number == sequence_number {
+            continue;
+        }
+        // The application might have the clipboard open while it's copying,
+        // so ignore errors and just try again later.
+        if let Ok(text) = get_clipboard_text() {
+            copied = Some((current_sequence_number, text));
+            break;
+        }
+    }
+    // If nothing is selected, the application may not copy anything, in
+    // which case the clipboard hasn't changed and there's nothing to restore.
+    let Some((copied_sequence_number, text)) = copied else {
+        return Ok(None);
+    };
+    if windows_util::get_clipboard_sequence_number() == copied_sequence_number {
+        windows_util::restore_clipboard(&original)?;
+    }
+    Ok(text.filter(|text| !text.is_empty()))
+}
+
 pub fn get_foreground_executable_path() -> Result<String, Error> {
     windows_util::get_foreground_executable_path()
 }
@@ -183,3 +384,30 @@ pub fn get_foreground_executable_path() -> Result<String, Error> {
 pub fn get_foreground_window_name() -> Result<String, Error> {
     windows_util::get_foreground_window_name()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_into_batches_works() {
+        assert_eq!(split_into_batches("abcde", 2), vec!["ab", "cd", "e"]);
+        assert!(split_into_batches("", 2).is_empty());
+    }
+
+    #[test]
+    fn test_split_into_batches_keeps_graphemes_together() {
+        // A surrogate pair, a letter with a combining accent, and a flag.
+        let text = "\u{1F600}e\u{301}\u{1F1E8}\u{1F1E6}";
+        assert_eq!(
+            split_into_batches(text, 3),
+            vec!["\u{1F600}", "e\u{301}", "\u{1F1E8}\u{1F1E6}"]
+        );
+    }
+
+    #[test]
+    fn test_split_into_batches_keeps_zwj_sequences_together() {
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        assert_eq!(split_into_batches(family, 2), vec![family]);
+    }
+}
@@ -0,0 +1,249 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+use super::command::CommandOutput;
+use super::error::Error;
+
+/// A callback that wakes up whoever is polling the task, e.g. by kicking
+/// the event loop.
+pub type Notifier = Arc<dyn Fn() + Send + Sync>;
+
+#[derive(Debug, PartialEq)]
+pub enum TaskEvent {
+    /// The task wants to tell the user what it's currently doing.
+    Progress(String),
+    /// The task is done, and its output should be performed by the UI. Errors
+    /// are stringified because our `Error` type can't be sent across threads.
+    Finished(Result<CommandOutput, String>),
+}
+
+/// This is given to a task's worker thread so it can report progress and
+/// find out whether it's been cancelled.
+pub struct TaskContext {
+    sender: Sender<TaskEvent>,
+    cancelled: Arc<AtomicBool>,
+    notify: Notifier,
+}
+
+impl TaskContext {
+    fn send(&self, event: TaskEvent) {
+        // If the receiving end is gone, nobody cares about this task anymore,
+        // so it's fine to drop the event on the floor.
+        if self.sender.send(event).is_ok() {
+            (self.notify)();
+        }
+    }
+
+    pub fn set_status<S: Into<String>>(&self, status: S) {
+        self.send(TaskEvent::Progress(status.into()));
+    }
+
+    /// Long-running tasks should check this periodically and bail if it
+    /// returns true.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// A handle to a task running on a worker thread.
+pub struct Task {
+    name: String,
+    receiver: Receiver<TaskEvent>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Task {
+    pub fn spawn<F, R>(name: &str, notify: Notifier, f: F) -> Result<Self, Error>
+    where
+        F: FnOnce(&TaskContext) -> Result<R, Error> + Send + 'static,
+        R: Into<CommandOutput>,
+    {
+        let (sender, receiver) = channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let ctx = TaskContext {
+            sender,
+            cancelled: cancelled.clone(),
+            notify,
+        };
+        thread::Builder::new()
+            .name(format!("Task: {}", name))
+            .spawn(move || {
+                let result = f(&ctx)
+                    .map(|output| output.into())
+                    .map_err(|err| err.to_string());
+                ctx.send(TaskEvent::Finished(result));
+            })?;
+        Ok(Task {
+            name: name.to_string(),
+            receiver,
+            cancelled,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns the next event sent by the task, if any.
+    pub fn try_recv(&self) -> Option<TaskEvent> {
+        match self.receiver.try_recv() {
+            Ok(event) => Some(event),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(TaskEvent::Finished(Err(String::from(
+                "The task stopped unexpectedly.",
+            )))),
+        }
+    }
+}
+
+/// Work done on a worker thread whose result is needed back on the thread
+/// that started it, e.g. data a plugin loads so it doesn't slow down startup.
+/// Unlike a task, it doesn't produce output for the user.
+pub struct BackgroundLoad<T> {
+    receiver: Receiver<Result<T, String>>,
+}
+
+impl<T: Send + 'static> BackgroundLoad<T> {
+    pub fn spawn<F>(name: &str, notify: Notifier, f: F) -> Result<Self, Error>
+    where
+        F: FnOnce() -> Result<T, Error> + Send + 'static,
+    {
+        let (sender, receiver) = channel();
+        thread::Builder::new()
+            .name(format!("Load: {}", name))
+            .spawn(move || {
+                let result = f().map_err(|err| err.to_string());
+                if sender.send(result).is_ok() {
+                    notify();
+                }
+            })?;
+        Ok(BackgroundLoad { receiver })
+    }
+
+    /// Returns the result of the work once it's done.
+    pub fn try_take(&self) -> Option<Result<T, String>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                Some(Err(String::from("Loading stopped unexpectedly.")))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn noop_notifier() -> Notifier {
+        Arc::new(|| {})
+    }
+
+    fn wait_for_event(task: &Task) -> TaskEvent {
+        loop {
+            if let Some(event) = task.try_recv() {
+                return event;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_task_reports_progress_and_success() {
+        let task = Task::spawn("boop", noop_notifier(), |ctx| {
+            ctx.set_status("booping");
+            Ok(CommandOutput::ShowMessage(String::from("booped")))
+        })
+        .unwrap();
+        assert_eq!(task.name(), "boop");
+        assert_eq!(
+            wait_for_event(&task),
+            TaskEvent::Progress(String::from("booping"))
+        );
+        assert_eq!(
+            wait_for_event(&task),
+            TaskEvent::Finished(Ok(CommandOutput::ShowMessage(String::from("booped"))))
+        );
+    }
+
+    #[test]
+    fn test_task_reports_errors() {
+        let task = Task::spawn("boop", noop_notifier(), |_ctx| -> Result<(), Error> {
+            Err(Error::new("nope"))
+        })
+        .unwrap();
+        match wait_for_event(&task) {
+            TaskEvent::Finished(Err(msg)) => assert!(msg.contains("nope")),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_task_reports_panics() {
+        let task = Task::spawn("boop", noop_notifier(), |_ctx| -> Result<(), Error> {
+            panic!("oof")
+        })
+        .unwrap();
+        assert_eq!(
+            wait_for_event(&task),
+            TaskEvent::Finished(Err(String::from("The task stopped unexpectedly.")))
+        );
+    }
+
+    #[test]
+    fn test_task_can_be_cancelled() {
+        let task = Task::spawn("boop", noop_notifier(), |ctx| {
+            while !ctx.is_cancelled() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            Err::<(), Error>(Error::new("cancelled"))
+        })
+        .unwrap();
+        task.cancel();
+        match wait_for_event(&task) {
+            TaskEvent::Finished(Err(msg)) => assert!(msg.contains("cancelled")),
+            _ => panic!(),
+        }
+    }
+
+    fn wait_for_load<T: Send + 'static>(load: &BackgroundLoad<T>) -> Result<T, String> {
+        loop {
+            if let Some(result) = load.try_take() {
+                return result;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_background_load_returns_its_result() {
+        let load = BackgroundLoad::spawn("boop", noop_notifier(), || Ok(5)).unwrap();
+        assert_eq!(wait_for_load(&load), Ok(5));
+        let load = BackgroundLoad::spawn("boop", noop_notifier(), || -> Result<u8, Error> {
+            panic!("oof")
+        })
+        .unwrap();
+        assert_eq!(
+            wait_for_load(&load),
+            Err(String::from("Loading stopped unexpectedly."))
+        );
+    }
+
+    #[test]
+    fn test_notifier_is_called() {
+        let (tx, rx) = channel();
+        let notify: Notifier = Arc::new(move || {
+            let _ = tx.send(());
+        });
+        let _task = Task::spawn("boop", notify, |_ctx| Ok(())).unwrap();
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    }
+}
@@ -4,25 +4,54 @@ use direct2d::math::ColorF;
 use direct2d::render_target::RenderTarget;
 use directwrite::factory::Factory;
 use directwrite::{TextFormat, TextLayout};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::ops::Range;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{Receiver, TryRecvError};
-use winapi::um::winuser::{VK_BACK, VK_DOWN, VK_UP};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use winapi::um::winuser::{VK_DOWN, VK_LEFT, VK_RIGHT, VK_UP};
 
-use crate::command::SimpleCommand;
+use crate::command::{CommandContext, CommandOutput, SimpleCommand};
+use crate::config::Config;
+use crate::pipeline::{self, Pipeline};
+use crate::prompt::Prompt;
+use crate::system::{
+    get_foreground_executable_path, insert_text, open_in_explorer, set_clipboard_text,
+    InsertionMethod,
+};
 
-use super::autocomplete_map::{AutocompleteMap, AutocompleteSuggestion};
+use super::autocomplete_map::AutocompleteSuggestion;
 use super::command::Command;
+use super::command_registry::CommandRegistry;
 use super::directx::Direct3DDevice;
 use super::error::Error;
+use super::event_loop::kick_event_loop;
+use super::grid::{get_cell_text, Direction};
 use super::keyboard_hook::HookEvent;
 use super::menu::Menu;
+use super::panics::{self, CaughtPanic};
+use super::task::{BackgroundLoad, Notifier, Task, TaskContext, TaskEvent};
 use super::transparent_window::TransparentWindow;
-use super::windows_util::{get_primary_screen_size, vkey_to_char};
+use super::windows_util::{
+    edit_input, get_primary_screen_size, kill_thread_timer, set_thread_timer,
+};
 
 type ColorAlpha = (u32, f32);
 
 const MAX_SUGGESTIONS: usize = 5;
+/// How many characters are suggested at once when they're shown in a grid.
+const MAX_GRID_SUGGESTIONS: usize = 40;
+const GRID_COLUMNS: usize = 10;
+const GRID_CELL_SIZE: f32 = FONT_SIZE * 1.5;
+/// How long the user has to stop typing before we preview the selected command.
+const PREVIEW_DELAY_MS: u64 = 100;
+/// How many times a plugin can panic before we disable it.
+const MAX_PLUGIN_PANICS: u32 = 3;
 const PADDING: f32 = 16.0;
 const PADDING_X2: f32 = PADDING * 2.0;
 const DEFAULT_BG: ColorAlpha = (0x00_00_00, 0.75);
@@ -130,7 +159,7 @@ impl QuasimodeRenderer {
     pub fn draw(
         &mut self,
         input: &String,
-        optional_menu: &Option<Menu<AutocompleteSuggestion<Box<dyn Command>>>>,
+        optional_menu: &Option<Menu<AutocompleteSuggestion<Pipeline>>>,
         help_text: &String,
         dw_factory: &Factory,
         text_format: &TextFormat,
@@ -143,10 +172,38 @@ impl QuasimodeRenderer {
             .with_size(screen_width as f32, screen_height as f32)
             .build()?;
         let mut menu_layouts: Vec<(TextLayout, bool, Vec<Range<usize>>)> = vec![];
-        if let Some(menu) = optional_menu {
+        let mut cell_layouts: Vec<(TextLayout, bool)> = vec![];
+        let grid = optional_menu.as_ref().and_then(|menu| menu.grid().copied());
+        if let (Some(menu), Some(_)) = (optional_menu, grid) {
+            // The grid doesn't show what was typed, so it goes above it.
+            let cmd_layout = TextLayout::create(dw_factory)
+                .with_text(input)
+                .with_font(text_format)
+                .with_size(screen_width as f32, screen_height as f32)
+                .build()?;
+            menu_layouts.push((cmd_layout, true, vec![0..input.len()]));
+            for (sugg, is_selected) in menu.iter() {
+                let text = match (sugg.value.label(), sugg.value.final_argument()) {
+                    (Some(label), _) => get_cell_text(label),
+                    (None, Some(argument)) => get_cell_text(argument),
+                    (None, None) => get_cell_text(&sugg.name),
+                };
+                let cell_layout = TextLayout::create(dw_factory)
+                    .with_text(text)
+                    .with_font(text_format)
+                    .with_size(GRID_CELL_SIZE, GRID_CELL_SIZE)
+                    .with_centered(true)
+                    .build()?;
+                cell_layouts.push((cell_layout, is_selected));
+            }
+        } else if let Some(menu) = optional_menu {
             for (sugg, is_selected) in menu.iter() {
+                let text = match sugg.value.label() {
+                    Some(label) => format!("{}  {}", sugg.name, label),
+                    None => sugg.name.clone(),
+                };
                 let menu_layout = TextLayout::create(dw_factory)
-                    .with_text(sugg.name.as_ref())
+                    .with_text(&text)
                     .with_font(text_format)
                     .with_size(screen_width as f32, screen_height as f32)
                     .build()?;
@@ -202,6 +259,42 @@ impl QuasimodeRenderer {
                 );
                 y = new_y;
             }
+            if let Some(grid) = grid {
+                let (grid_width, grid_height) = grid.size(GRID_CELL_SIZE);
+                target.fill_rectangle(
+                    (
+                        0.0,
+                        y,
+                        grid_width + PADDING_X2,
+                        y + grid_height + PADDING_X2,
+                    ),
+                    &brushes.default_bg,
+                );
+                for (idx, (cell_layout, is_selected)) in cell_layouts.into_iter().enumerate() {
+                    let (cell_x, cell_y) = grid.cell_origin(idx, GRID_CELL_SIZE);
+                    let (cell_x, cell_y) = (cell_x + PADDING, cell_y + y + PADDING);
+                    if is_selected {
+                        target.fill_rectangle(
+                            (
+                                cell_x,
+                                cell_y,
+                                cell_x + GRID_CELL_SIZE,
+                                cell_y + GRID_CELL_SIZE,
+                            ),
+                            &brushes.help_bg,
+                        );
+                    }
+                    // Layouts are only centered horizontally, so this
+                    // centers them vertically.
+                    let text_height = cell_layout.get_metrics().height();
+                    target.draw_text_layout(
+                        (cell_x, cell_y + (GRID_CELL_SIZE - text_height) / 2.0),
+                        &cell_layout,
+                        &brushes.default_fg,
+                        DrawTextOptions::NONE,
+                    );
+                }
+            }
             Ok(())
         })?;
         Ok(())
@@ -210,6 +303,14 @@ impl QuasimodeRenderer {
 
 #[allow(unused_variables)]
 pub trait UserInterfacePlugin {
+    /// A short name for the plugin, which is used to enable or disable it.
+    fn name(&self) -> String;
+
+    /// Whether the user is allowed to disable the plugin.
+    fn can_be_disabled(&self) -> bool {
+        true
+    }
+
     fn init(&mut self, ui: &mut UserInterface) -> Result<(), Error> {
         Ok(())
     }
@@ -217,8 +318,89 @@ pub trait UserInterfacePlugin {
     fn on_quasimode_start(&mut self, ui: &mut UserInterface) -> Result<(), Error> {
         Ok(())
     }
+
+    /// Called whenever the user types or deletes a character in the quasimode.
+    fn on_input_changed(&mut self, ui: &mut UserInterface, input: &str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called when the quasimode ends, before the selected command (if any) is run.
+    fn on_quasimode_end(&mut self, ui: &mut UserInterface) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn on_command_executed(
+        &mut self,
+        ui: &mut UserInterface,
+        name: &str,
+        output: &CommandOutput,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn on_command_failed(
+        &mut self,
+        ui: &mut UserInterface,
+        name: &str,
+        error: &Error,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn on_message_dismissed(&mut self, ui: &mut UserInterface) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called right before Enso exits, so plugins can save any state they need to.
+    fn on_shutdown(&mut self, ui: &mut UserInterface) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Plugins are shared so that the UI can call into them while they're calling
+/// into the UI, e.g. if a plugin adds another plugin from one of its hooks.
+type SharedPlugin = Rc<RefCell<Box<dyn UserInterfacePlugin>>>;
+
+struct LoadedPlugin {
+    name: String,
+    plugin: SharedPlugin,
+}
+
+/// A plugin Enso knows about, which is only built if it's going to be loaded,
+/// so that listing plugins doesn't have to build all of them.
+pub struct PluginEntry {
+    pub name: String,
+    pub build: Box<dyn Fn() -> Box<dyn UserInterfacePlugin>>,
+}
+
+impl PluginEntry {
+    pub fn new<F>(name: impl Into<String>, build: F) -> Self
+    where
+        F: Fn() -> Box<dyn UserInterfacePlugin> + 'static,
+    {
+        PluginEntry {
+            name: name.into(),
+            build: Box::new(build),
+        }
+    }
+}
+
+/// The work of a task that's waiting for an earlier one with the same name.
+type QueuedTask = Box<dyn FnOnce(&TaskContext) -> Result<CommandOutput, Error> + Send>;
+
+/// Finishes a background load if it's done, returning `None` if it isn't yet.
+type FinishLoad = Box<dyn FnMut(&mut UserInterface) -> Option<Result<(), Error>>>;
+
+/// Something a plugin is loading on a worker thread, along with what to do
+/// with it on the UI thread once it's loaded.
+struct PendingLoad {
+    plugin: Option<String>,
+    finish: FinishLoad,
 }
 
+/// Returns every plugin Enso knows about, whether or not it's enabled.
+pub type PluginFactory = fn() -> Vec<PluginEntry>;
+
 pub struct UserInterface {
     input: String,
     should_quit: bool,
@@ -228,13 +410,32 @@ pub struct UserInterface {
     small_text_format: TextFormat,
     quasimode: Option<QuasimodeRenderer>,
     message: Option<TransparentMessageRenderer>,
-    menu: Option<Menu<AutocompleteSuggestion<Box<dyn Command>>>>,
-    commands: AutocompleteMap<Box<dyn Command>>,
-    plugins: Option<Vec<Box<dyn UserInterfacePlugin>>>,
+    menu: Option<Menu<AutocompleteSuggestion<Pipeline>>>,
+    prompt: Option<Prompt>,
+    context: CommandContext,
+    /// The name of the menu entry that was last previewed, along with its preview.
+    preview: Option<(String, Option<String>)>,
+    preview_due: Option<Instant>,
+    /// The timer that wakes up the event loop when the preview is due.
+    preview_timer: Option<usize>,
+    /// Set from other threads when a command's preview has changed.
+    preview_changed: Arc<AtomicBool>,
+    commands: CommandRegistry,
+    plugins: Vec<LoadedPlugin>,
+    plugin_factory: Option<PluginFactory>,
+    /// The plugin whose code is currently running, if any.
+    current_plugin: Option<String>,
+    /// How many times each plugin has panicked since it was loaded.
+    plugin_panics: HashMap<String, u32>,
+    config: Config,
+    tasks: Vec<Task>,
+    queued_tasks: Vec<(String, QueuedTask)>,
+    pending_loads: Vec<PendingLoad>,
+    event_loop_thread_id: u32,
 }
 
 impl UserInterface {
-    pub fn new(d3d_device: Direct3DDevice) -> Result<Self, Error> {
+    pub fn new(d3d_device: Direct3DDevice, event_loop_thread_id: u32) -> Result<Self, Error> {
         let dw_factory = Factory::new()?;
         let text_format = TextFormat::create(&dw_factory)
             .with_family(FONT_FAMILY)
@@ -254,43 +455,355 @@ impl UserInterface {
             quasimode: None,
             message: None,
             menu: None,
-            commands: AutocompleteMap::new(),
-            plugins: Some(vec![]),
+            prompt: None,
+            context: CommandContext::default(),
+            preview: None,
+            preview_due: None,
+            preview_timer: None,
+            preview_changed: Arc::new(AtomicBool::new(false)),
+            commands: CommandRegistry::new(),
+            plugins: vec![],
+            plugin_factory: None,
+            current_plugin: None,
+            plugin_panics: HashMap::new(),
+            config: Config::default(),
+            tasks: vec![],
+            queued_tasks: vec![],
+            pending_loads: vec![],
+            event_loop_thread_id,
         };
         Ok(ui)
     }
 
-    pub fn add_plugin(&mut self, mut plugin: Box<dyn UserInterfacePlugin>) -> Result<(), Error> {
-        with_plugins(self, move |ui, plugins| {
-            plugin.init(ui)?;
-            plugins.push(plugin);
-            Ok(())
-        })
+    /// Loads every plugin the given factory returns, unless it's disabled in
+    /// the configuration.
+    pub fn load_plugins(&mut self, factory: PluginFactory) -> Result<(), Error> {
+        self.plugin_factory = Some(factory);
+        self.reload_plugins()
+    }
+
+    /// Unloads all plugins, then loads them again according to the latest
+    /// configuration.
+    pub fn reload_plugins(&mut self) -> Result<(), Error> {
+        let Some(factory) = self.plugin_factory else {
+            return Ok(());
+        };
+        self.config = Config::load()?;
+        self.commands
+            .set_preferred_plugins(self.config.get_preferred_plugins());
+        let warnings = self.config.warnings();
+        if !warnings.is_empty() {
+            let message = format!(
+                "Problems occurred while parsing the configuration file:\n{}",
+                warnings.join("\n")
+            );
+            eprintln!("{}", message);
+            self.show_message(message)?;
+        }
+        let mut first_error = None;
+        let names: Vec<String> = self.plugins.iter().map(|p| p.name.clone()).collect();
+        for name in names {
+            if let Err(error) = self.unload_plugin(&name) {
+                first_error.get_or_insert(error);
+            }
+        }
+        for entry in factory() {
+            if self.config.is_plugin_disabled(&entry.name) {
+                println!("Not loading disabled plugin \"{}\".", entry.name);
+                continue;
+            }
+            if let Err(error) = self.add_plugin((entry.build)()) {
+                first_error.get_or_insert(error);
+            }
+        }
+        match first_error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    fn is_plugin_loaded(&self, name: &str) -> bool {
+        self.plugins.iter().any(|loaded| loaded.name == name)
+    }
+
+    /// Returns the context the current or most recent quasimode started in.
+    pub fn context(&self) -> &CommandContext {
+        &self.context
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Returns the name of every plugin Enso knows about, and whether it's enabled.
+    pub fn get_plugin_names(&self) -> Vec<(String, bool)> {
+        let Some(factory) = self.plugin_factory else {
+            return vec![];
+        };
+        factory()
+            .into_iter()
+            .map(|entry| {
+                let is_loaded = self.is_plugin_loaded(&entry.name);
+                (entry.name, is_loaded)
+            })
+            .collect()
+    }
+
+    pub fn enable_plugin(&mut self, name: &str) -> Result<(), Error> {
+        if self.is_plugin_loaded(name) {
+            return Err(Error::new(format!(
+                "The \u{201C}{}\u{201D} plugin is already enabled.",
+                name
+            )));
+        }
+        let plugin = self
+            .plugin_factory
+            .and_then(|factory| factory().into_iter().find(|entry| entry.name == name))
+            .map(|entry| (entry.build)())
+            .ok_or_else(|| {
+                Error::new(format!(
+                    "There's no plugin called \u{201C}{}\u{201D}.",
+                    name
+                ))
+            })?;
+        self.config.set_plugin_disabled(name, false)?;
+        self.add_plugin(plugin)
+    }
+
+    pub fn disable_plugin(&mut self, name: &str) -> Result<(), Error> {
+        let Some(loaded) = self.plugins.iter().find(|loaded| loaded.name == name) else {
+            return Err(Error::new(format!(
+                "The \u{201C}{}\u{201D} plugin isn't enabled.",
+                name
+            )));
+        };
+        let can_be_disabled = match loaded.plugin.try_borrow() {
+            Ok(plugin) => plugin.can_be_disabled(),
+            Err(_) => false,
+        };
+        if !can_be_disabled {
+            return Err(Error::new(format!(
+                "The \u{201C}{}\u{201D} plugin can't be disabled.",
+                name
+            )));
+        }
+        self.config.set_plugin_disabled(name, true)?;
+        self.unload_plugin(name)
+    }
+
+    pub fn add_plugin(&mut self, plugin: Box<dyn UserInterfacePlugin>) -> Result<(), Error> {
+        let name = plugin.name();
+        if self.is_plugin_loaded(&name) {
+            return Err(Error::new(format!(
+                "A plugin called \u{201C}{}\u{201D} is already loaded.",
+                name
+            )));
+        }
+        self.plugin_panics.remove(&name);
+        let plugin: SharedPlugin = Rc::new(RefCell::new(plugin));
+        let result = self
+            .run_plugin_code(Some(name.clone()), |ui| plugin.borrow_mut().init(ui))
+            .unwrap_or_else(|panic| Err(Error::new(panic)));
+        match result {
+            Ok(()) => {
+                self.plugins.push(LoadedPlugin { name, plugin });
+                Ok(())
+            }
+            Err(error) => {
+                self.remove_plugin_commands(&name);
+                Err(error)
+            }
+        }
+    }
+
+    /// Removes the given plugin along with every command it added, after giving
+    /// it a chance to clean up. Once nothing else refers to the plugin, its
+    /// state is dropped.
+    fn unload_plugin(&mut self, name: &str) -> Result<(), Error> {
+        let Some(idx) = self.plugins.iter().position(|loaded| loaded.name == name) else {
+            return Ok(());
+        };
+        println!("Unloading plugin \"{}\".", name);
+        let loaded = self.plugins.remove(idx);
+        self.pending_loads
+            .retain(|load| load.plugin.as_deref() != Some(name));
+        let result = match loaded.plugin.try_borrow_mut() {
+            Ok(mut plugin) => self
+                .run_plugin_code(Some(loaded.name.clone()), |ui| plugin.on_shutdown(ui))
+                .unwrap_or_else(|panic| Err(Error::new(panic))),
+            // The plugin is busy, most likely because it's the one that
+            // panicked and is being disabled.
+            Err(_) => Ok(()),
+        };
+        self.remove_plugin_commands(name);
+        result
+    }
+
+    fn remove_plugin_commands(&mut self, plugin_name: &str) {
+        self.commands.remove_plugin(plugin_name);
+    }
+
+    /// Calls the given function on every plugin. A plugin that's already busy,
+    /// because this is being called from one of its own hooks, is skipped.
+    ///
+    /// Every plugin is called even if some of them fail. Their errors and
+    /// panics are reported to the user rather than returned, so that a
+    /// misbehaving plugin can't take the rest of Enso down with it. Only the
+    /// UI's own failures, e.g. being unable to show a message, are returned.
+    fn notify_plugins<F>(&mut self, mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(&mut dyn UserInterfacePlugin, &mut UserInterface) -> Result<(), Error>,
+    {
+        // Iterate over a copy of the list, since plugins may be added while
+        // we're notifying them.
+        let plugins: Vec<(String, SharedPlugin)> = self
+            .plugins
+            .iter()
+            .map(|loaded| (loaded.name.clone(), loaded.plugin.clone()))
+            .collect();
+        for (name, plugin) in plugins {
+            let Ok(mut plugin) = plugin.try_borrow_mut() else {
+                continue;
+            };
+            match self.run_plugin_code(Some(name.clone()), |ui| f(plugin.as_mut(), ui)) {
+                Ok(Ok(())) => {}
+                // A plugin's problems shouldn't stop Enso or the other
+                // plugins, so they're only reported.
+                Ok(Err(error)) => {
+                    eprintln!("Error in plugin hook: {}", error);
+                    self.show_message(format!(
+                        "The \u{201C}{}\u{201D} plugin ran into a problem:\n{}",
+                        name, error
+                    ))?;
+                }
+                Err(panic) => self.show_message(panic)?,
+            }
+        }
+        Ok(())
     }
 
-    pub fn add_simple_command(
+    /// Runs the given code on behalf of the given plugin, so that any commands
+    /// it adds are attributed to the plugin.
+    ///
+    /// If the code panics, the panic is logged and a description of it is
+    /// returned. A plugin that panics too often is disabled, since whatever
+    /// state it has is probably broken.
+    fn run_plugin_code<T, F>(&mut self, plugin_name: Option<String>, f: F) -> Result<T, String>
+    where
+        F: FnOnce(&mut UserInterface) -> T,
+    {
+        let previous_plugin = std::mem::replace(&mut self.current_plugin, plugin_name.clone());
+        let result = panics::catch_panic(|| f(self));
+        self.current_plugin = previous_plugin;
+        result.map_err(|panic| self.handle_panic(plugin_name, panic))
+    }
+
+    fn handle_panic(&mut self, plugin_name: Option<String>, panic: CaughtPanic) -> String {
+        let culprit = match &plugin_name {
+            Some(name) => format!("The \u{201C}{}\u{201D} plugin", name),
+            None => String::from("Enso"),
+        };
+        if let Err(error) = panics::log_panic(&culprit, &panic) {
+            eprintln!("Unable to log panic: {}", error);
+        }
+        let mut description = format!("{} crashed: {}", culprit, panic);
+        let Some(name) = plugin_name else {
+            return description;
+        };
+        let panic_count = self.plugin_panics.entry(name.clone()).or_insert(0);
+        *panic_count += 1;
+        let can_be_disabled = self
+            .plugins
+            .iter()
+            .find(|loaded| loaded.name == name)
+            .is_some_and(|loaded| {
+                loaded
+                    .plugin
+                    .try_borrow()
+                    .map_or(true, |plugin| plugin.can_be_disabled())
+            });
+        if *panic_count >= MAX_PLUGIN_PANICS && can_be_disabled {
+            println!("Disabling plugin \"{}\" because it keeps panicking.", name);
+            if let Err(error) = self.unload_plugin(&name) {
+                eprintln!("Error unloading plugin: {}", error);
+            }
+            description.push_str(&format!(
+                "\nIt kept crashing, so it's been turned off. \
+                 Use \u{201C}enable plugin {}\u{201D} to turn it back on.",
+                name
+            ));
+        }
+        description
+    }
+
+    /// Runs code belonging to a command that the given plugin added, reporting
+    /// a panic as an error that blames the plugin.
+    pub fn run_command_code<T, F>(&mut self, plugin_name: Option<String>, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut UserInterface) -> Result<T, Error>,
+    {
+        self.run_plugin_code(plugin_name, f)
+            .unwrap_or_else(|panic| Err(Error::new(panic)))
+    }
+
+    /// Lets plugins know that Enso is about to exit.
+    pub fn shutdown(&mut self) -> Result<(), Error> {
+        self.notify_plugins(|plugin, ui| plugin.on_shutdown(ui))
+    }
+
+    pub fn add_simple_command<R: Into<CommandOutput> + 'static>(
         &mut self,
         name: &str,
-        callback: impl FnMut(&mut UserInterface) -> Result<(), Error> + Clone + 'static,
+        callback: impl FnMut(&mut UserInterface) -> Result<R, Error> + Clone + 'static,
     ) {
         self.add_command(Box::new(SimpleCommand::new(name, callback)));
     }
 
+    /// Removes the command with the given name that belongs to the plugin
+    /// whose code is currently running.
     pub fn remove_command<T: AsRef<str>>(&mut self, command_name: T) -> Option<Box<dyn Command>> {
-        self.commands.remove(command_name)
+        self.commands
+            .remove(self.current_plugin.as_deref(), command_name.as_ref())
     }
 
+    /// Returns whether the plugin whose code is currently running has a
+    /// command with the given name.
     pub fn has_command<T: AsRef<str>>(&mut self, command_name: T) -> bool {
-        self.commands.contains(command_name)
+        self.commands
+            .contains(self.current_plugin.as_deref(), command_name.as_ref())
     }
 
-    /// Adds the given command to the UI *only* if the given command doesn't yet exist.
+    /// Adds the given command to the UI on behalf of the plugin whose code is
+    /// currently running, *only* if that plugin doesn't have a command with
+    /// the same name yet.
     ///
-    /// If the command already exists, nothing happens.
+    /// Commands from other plugins with the same name don't stop it from
+    /// being added, since the user can tell them apart by their plugin names.
     pub fn add_command(&mut self, command: Box<dyn Command>) {
         let command_name = command.name();
-        if !self.has_command(&command_name) {
-            self.commands.insert(command_name, command);
+        if !self.commands.add(self.current_plugin.clone(), command) {
+            println!("Command \"{}\" already exists, skipping.", command_name);
+        }
+    }
+
+    /// Performs whatever the given command output asks for.
+    pub fn perform_output(&mut self, output: CommandOutput) -> Result<(), Error> {
+        match output {
+            CommandOutput::Nothing => Ok(()),
+            CommandOutput::InsertText(text) => {
+                let method = get_foreground_executable_path()
+                    .map(|path| self.config.get_insertion_method(&path))
+                    .unwrap_or(InsertionMethod::Type);
+                insert_text(&text, method)
+            }
+            CommandOutput::ShowMessage(text) => self.show_message(text),
+            CommandOutput::Open(target) => open_in_explorer(&PathBuf::from(target)),
+            CommandOutput::CopyText(text) => set_clipboard_text(&text),
+            CommandOutput::Prompt(prompt) => {
+                let message = format!("{}\nHold down CAPS LOCK to answer.", prompt.message());
+                self.prompt = Some(prompt);
+                self.show_message(message)
+            }
         }
     }
 
@@ -313,10 +826,185 @@ impl UserInterface {
         Ok(())
     }
 
+    /// Runs the given function on a worker thread, so that slow commands don't
+    /// hold up the event loop. While it's running, a "cancel <name>" command is
+    /// available, and any status updates it sends are shown as messages.
+    ///
+    /// If a task with the same name is already running, this one waits for it
+    /// to finish, so that e.g. running a hotkey command twice in quick
+    /// succession doesn't interleave its keystrokes.
+    pub fn spawn_task<F, R>(&mut self, name: &str, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&TaskContext) -> Result<R, Error> + Send + 'static,
+        R: Into<CommandOutput>,
+    {
+        let plugin = self.current_plugin.clone();
+        let f: QueuedTask =
+            Box::new(move |ctx| f(ctx).map(|output| output.into().with_plugin(plugin)));
+        if self.tasks.iter().any(|task| task.name() == name) {
+            self.queued_tasks.push((name.to_string(), f));
+            return Ok(());
+        }
+        self.start_task(name, f)
+    }
+
+    fn start_task(&mut self, name: &str, f: QueuedTask) -> Result<(), Error> {
+        let thread_id = self.event_loop_thread_id;
+        let task = Task::spawn(name, Arc::new(move || kick_event_loop(thread_id)), f)?;
+        self.tasks.push(task);
+        let task_name = name.to_string();
+        // This belongs to Enso itself rather than any plugin, so it can be
+        // removed once the task is done.
+        let cancel_command = SimpleCommand::new(cancel_command_name(name), move |ui| {
+            ui.cancel_task(&task_name)
+        });
+        self.commands.add(None, Box::new(cancel_command));
+        Ok(())
+    }
+
+    /// Returns a callback that can be called from any thread when a command's
+    /// preview has changed, e.g. because it was fetched in the background, so
+    /// that the selected menu entry is previewed again.
+    pub fn preview_change_notifier(&self) -> Notifier {
+        let preview_changed = self.preview_changed.clone();
+        let thread_id = self.event_loop_thread_id;
+        Arc::new(move || {
+            preview_changed.store(true, Ordering::SeqCst);
+            kick_event_loop(thread_id);
+        })
+    }
+
+    /// Runs `load` on a worker thread, then passes what it returns to `finish`
+    /// on the UI thread on behalf of the plugin whose code is currently
+    /// running. This lets a plugin load its data without slowing down Enso's
+    /// startup, and add the commands that need it once it's ready.
+    pub fn load_in_background<T, L, F>(
+        &mut self,
+        name: &str,
+        load: L,
+        finish: F,
+    ) -> Result<(), Error>
+    where
+        T: Send + 'static,
+        L: FnOnce() -> Result<T, Error> + Send + 'static,
+        F: FnOnce(&mut UserInterface, T) -> Result<(), Error> + 'static,
+    {
+        let thread_id = self.event_loop_thread_id;
+        let load = BackgroundLoad::spawn(name, Arc::new(move || kick_event_loop(thread_id)), load)?;
+        let mut finish = Some(finish);
+        self.pending_loads.push(PendingLoad {
+            plugin: self.current_plugin.clone(),
+            finish: Box::new(move |ui| {
+                let result = load.try_take()?;
+                let finish = finish.take()?;
+                Some(
+                    result
+                        .map_err(Error::new)
+                        .and_then(|value| finish(ui, value)),
+                )
+            }),
+        });
+        Ok(())
+    }
+
+    fn process_pending_loads(&mut self) -> Result<(), Error> {
+        for mut load in std::mem::take(&mut self.pending_loads) {
+            // The plugin may have been unloaded, e.g. because its `init`
+            // failed after it started loading.
+            if let Some(name) = &load.plugin {
+                if !self.is_plugin_loaded(name) {
+                    continue;
+                }
+            }
+            let plugin = load.plugin.clone();
+            match self.run_plugin_code(plugin.clone(), |ui| (load.finish)(ui)) {
+                Ok(None) => self.pending_loads.push(load),
+                Ok(Some(Ok(()))) => {}
+                Ok(Some(Err(error))) => {
+                    eprintln!("Error loading plugin: {}", error);
+                    self.show_message(format!(
+                        "The \u{201C}{}\u{201D} plugin couldn't finish loading:\n{}",
+                        plugin.unwrap_or_default(),
+                        error
+                    ))?;
+                }
+                Err(panic) => self.show_message(panic)?,
+            }
+        }
+        Ok(())
+    }
+
+    pub fn cancel_task(&mut self, name: &str) -> Result<(), Error> {
+        self.queued_tasks
+            .retain(|(queued_name, _)| queued_name != name);
+        if let Some(task) = self.remove_task(name) {
+            task.cancel();
+            self.show_message(format!("Cancelled \u{201C}{}\u{201D}.", name))?;
+        }
+        Ok(())
+    }
+
+    fn remove_task(&mut self, name: &str) -> Option<Task> {
+        let idx = self.tasks.iter().position(|task| task.name() == name)?;
+        self.commands.remove(None, &cancel_command_name(name));
+        Some(self.tasks.remove(idx))
+    }
+
+    /// Starts the next task that was waiting for the one with the given name
+    /// to finish, if any.
+    fn start_queued_task(&mut self, name: &str) -> Result<(), Error> {
+        let Some(idx) = self
+            .queued_tasks
+            .iter()
+            .position(|(queued_name, _)| queued_name == name)
+        else {
+            return Ok(());
+        };
+        let (_, f) = self.queued_tasks.remove(idx);
+        self.start_task(name, f)
+    }
+
+    fn process_task_events(&mut self) -> Result<(), Error> {
+        let mut events: Vec<(String, TaskEvent)> = vec![];
+        for task in self.tasks.iter() {
+            while let Some(event) = task.try_recv() {
+                let is_finished = matches!(event, TaskEvent::Finished(_));
+                events.push((task.name().to_string(), event));
+                if is_finished {
+                    break;
+                }
+            }
+        }
+        for (name, event) in events {
+            match event {
+                TaskEvent::Progress(status) => {
+                    // Don't cover up the quasimode with status updates.
+                    if self.quasimode.is_none() {
+                        self.show_message(status)?;
+                    }
+                }
+                TaskEvent::Finished(result) => {
+                    self.remove_task(&name);
+                    self.start_queued_task(&name)?;
+                    match result {
+                        Ok(output) => self.perform_output(output)?,
+                        Err(error) => self.show_message(format!(
+                            "An error occurred when running the command:\n{}",
+                            error
+                        ))?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn process_event_receiver(
         &mut self,
         receiver: &Receiver<HookEvent>,
     ) -> Result<bool, Error> {
+        self.process_task_events()?;
+        self.process_pending_loads()?;
         loop {
             match receiver.try_recv() {
                 Ok(event) => {
@@ -325,6 +1013,9 @@ impl UserInterface {
                     }
                 }
                 Err(TryRecvError::Empty) => {
+                    if self.update_preview() {
+                        self.redraw_quasimode()?;
+                    }
                     return Ok(false);
                 }
                 Err(TryRecvError::Disconnected) => {
@@ -338,36 +1029,60 @@ impl UserInterface {
         let mut redraw_quasimode = false;
         if self.message.is_some() {
             match event {
-                HookEvent::QuasimodeStart | HookEvent::QuasimodeEnd => self.message = None,
+                HookEvent::QuasimodeStart | HookEvent::QuasimodeEnd => {
+                    self.message = None;
+                    self.notify_plugins(|plugin, ui| plugin.on_message_dismissed(ui))?;
+                }
                 _ => {}
             }
         }
         match event {
             HookEvent::QuasimodeStart => {
                 println!("Starting quasimode.");
-                with_plugins(self, move |ui, plugins| {
-                    for plugin in plugins.iter_mut() {
-                        plugin.on_quasimode_start(ui)?;
-                    }
-                    Ok(())
-                })?;
+                self.notify_plugins(|plugin, ui| plugin.on_quasimode_start(ui))?;
                 self.input.clear();
+                self.context = CommandContext::current();
+                self.preview = None;
+                self.update_menu();
                 self.quasimode = Some(QuasimodeRenderer::new(&mut self.d3d_device)?);
                 redraw_quasimode = true;
             }
             HookEvent::QuasimodeEnd => {
                 println!("Ending quasimode.");
                 self.quasimode = None;
+                self.cancel_preview();
+                self.notify_plugins(|plugin, ui| plugin.on_quasimode_end(ui))?;
+                // Whether or not the user answered it, any prompt is over now.
+                let prompt = self.prompt.take();
                 if let Some(menu) = self.menu.take() {
-                    let mut sugg = menu.into_selected_entry();
+                    let AutocompleteSuggestion {
+                        name,
+                        value: mut pipeline,
+                        ..
+                    } = menu.into_selected_entry();
 
-                    if let Err(error) = sugg.value.execute(self) {
+                    let owner = pipeline.final_plugin();
+                    let result = pipeline.run(self).and_then(|output| {
+                        self.notify_plugins(|plugin, ui| {
+                            plugin.on_command_executed(ui, &name, &output)
+                        })?;
+                        self.perform_output(output.with_plugin(owner))
+                    });
+                    if let Err(error) = result {
+                        self.notify_plugins(|plugin, ui| {
+                            plugin.on_command_failed(ui, &name, &error)
+                        })?;
                         self.show_message(format!(
                             "An error occurred when running the command:\n{}",
                             error
                         ))?;
                     }
-                } else if self.input.len() > 0 {
+                } else if prompt.is_some() && !self.input.is_empty() {
+                    self.show_message(format!(
+                        "\u{201C}{}\u{201D} isn't one of the choices.",
+                        self.input
+                    ))?;
+                } else if !self.input.is_empty() {
                     println!("Unknown command '{}'.", self.input);
                     self.show_message(format!(
                         "Alas, I am unfamiliar with the \u{201C}{}\u{201D} command.",
@@ -375,82 +1090,151 @@ impl UserInterface {
                     ))?;
                 }
             }
-            HookEvent::Keypress(vk_code) => {
-                let input_changed = if vk_code == VK_BACK {
-                    match self.input.pop() {
-                        None => false,
-                        Some(_) => true,
-                    }
-                } else if let Some(ch) = vkey_to_char(vk_code) {
-                    for lch in ch.to_lowercase() {
-                        self.input.push(lch);
-                    }
-                    true
-                } else {
-                    false
-                };
-
-                if input_changed {
-                    let suggs = self.commands.autocomplete(&self.input, MAX_SUGGESTIONS);
-                    self.menu = if let Ok(menu) = Menu::try_from(suggs) {
-                        Some(menu)
-                    } else {
-                        None
-                    };
+            HookEvent::Keypress(vk_code, shift) => {
+                if edit_input(&mut self.input, vk_code, shift) {
+                    let input = self.input.clone();
+                    self.notify_plugins(|plugin, ui| plugin.on_input_changed(ui, &input))?;
+                    self.update_menu();
                     redraw_quasimode = true;
                 } else {
-                    match vk_code {
-                        VK_UP | VK_DOWN => {
-                            if let Some(menu) = &mut self.menu {
-                                redraw_quasimode = true;
-                                if vk_code == VK_UP {
-                                    menu.select_prev();
-                                } else {
-                                    menu.select_next();
-                                }
-                            }
-                        }
-                        _ => {}
+                    let direction = match vk_code {
+                        VK_UP => Some(Direction::Up),
+                        VK_DOWN => Some(Direction::Down),
+                        VK_LEFT => Some(Direction::Left),
+                        VK_RIGHT => Some(Direction::Right),
+                        _ => None,
+                    };
+                    if let (Some(direction), Some(menu)) = (direction, &mut self.menu) {
+                        menu.move_selection(direction);
+                        redraw_quasimode = true;
+                        self.schedule_preview();
                     }
                 }
             }
         };
         if redraw_quasimode {
-            if let Some(ref mut quasimode) = self.quasimode {
-                let help_text: String = if let Some(menu) = &self.menu {
-                    let cmd_name = menu.selected_entry().value.name();
-                    format!("Run the command \u{201C}{}\u{201D}.", cmd_name)
-                } else if self.input.len() > 0 {
-                    String::from(NOCMD_HELP)
-                } else {
-                    String::from(EMPTY_INPUT_HELP)
+            self.redraw_quasimode()?;
+        }
+        return Ok(self.should_quit);
+    }
+
+    fn redraw_quasimode(&mut self) -> Result<(), Error> {
+        if let Some(ref mut quasimode) = self.quasimode {
+            let help_text: String = if let Some(prompt) = &self.prompt {
+                prompt.message().to_string()
+            } else if let Some(menu) = &self.menu {
+                let sugg = menu.selected_entry();
+                let cmd_name = &sugg.name;
+                let preview = match &self.preview {
+                    Some((name, Some(preview))) if name == cmd_name => Some(preview),
+                    _ => None,
                 };
+                // Grid cells only show characters, so this says which one
+                // is selected.
+                match (menu.grid(), sugg.value.final_argument(), preview) {
+                    (Some(_), Some(argument), Some(preview)) => {
+                        format!("{}\n{}", argument, preview)
+                    }
+                    (Some(_), Some(argument), None) => argument.to_string(),
+                    (_, _, Some(preview)) => preview.clone(),
+                    _ => format!("Run the command \u{201C}{}\u{201D}.", cmd_name),
+                }
+            } else if !self.input.is_empty() {
+                String::from(NOCMD_HELP)
+            } else {
+                String::from(EMPTY_INPUT_HELP)
+            };
 
-                quasimode.draw(
-                    &self.input,
-                    &self.menu,
-                    &help_text,
-                    &self.dw_factory,
-                    &self.text_format,
-                    &self.small_text_format,
-                )?;
-            }
+            quasimode.draw(
+                &self.input,
+                &self.menu,
+                &help_text,
+                &self.dw_factory,
+                &self.text_format,
+                &self.small_text_format,
+            )?;
         }
-        return Ok(self.should_quit);
+        Ok(())
+    }
+
+    /// Arranges for the selected menu entry to be previewed once the user
+    /// pauses for a moment, so that computing previews doesn't slow down typing.
+    fn schedule_preview(&mut self) {
+        let is_preview_current = match (&self.menu, &self.preview) {
+            (Some(menu), Some((name, _))) => &menu.selected_entry().name == name,
+            _ => false,
+        };
+        if self.menu.is_none() || is_preview_current {
+            self.cancel_preview();
+            return;
+        }
+        self.preview_due = Some(Instant::now() + Duration::from_millis(PREVIEW_DELAY_MS));
+        // There's only ever one timer, which is restarted on each keystroke,
+        // so typing quickly doesn't create a pile of them.
+        match set_thread_timer(self.preview_timer, PREVIEW_DELAY_MS as u32) {
+            Ok(id) => self.preview_timer = Some(id),
+            Err(error) => eprintln!("Unable to start the preview timer: {}", error),
+        }
+    }
+
+    fn cancel_preview(&mut self) {
+        self.preview_due = None;
+        if let Some(id) = self.preview_timer.take() {
+            kill_thread_timer(id);
+        }
+    }
+
+    /// Previews the selected menu entry if it's time to, returning whether
+    /// the preview changed.
+    fn update_preview(&mut self) -> bool {
+        if self.preview_changed.swap(false, Ordering::SeqCst) && self.menu.is_some() {
+            self.preview_due = Some(Instant::now());
+        }
+        let Some(due) = self.preview_due else {
+            return false;
+        };
+        if Instant::now() < due {
+            return false;
+        }
+        self.cancel_preview();
+        let Some(menu) = &self.menu else {
+            return false;
+        };
+        let sugg = menu.selected_entry();
+        let name = sugg.name.clone();
+        let pipeline = sugg.value.clone();
+        // If the preview crashes, the user should at least see why.
+        let preview = self
+            .run_command_code(pipeline.final_plugin(), |_ui| Ok(pipeline.preview()))
+            .unwrap_or_else(|error| Some(error.to_string()));
+        self.preview = Some((name, preview));
+        true
+    }
+
+    fn update_menu(&mut self) {
+        let use_grid = self.prompt.is_none()
+            && pipeline::suggests_characters(&self.commands, &self.context, &self.input);
+        let suggs = if let Some(prompt) = &self.prompt {
+            prompt.autocomplete(&self.input, MAX_SUGGESTIONS)
+        } else if use_grid {
+            pipeline::autocomplete_arguments(
+                &self.commands,
+                &self.context,
+                &self.input,
+                MAX_GRID_SUGGESTIONS,
+            )
+        } else {
+            pipeline::autocomplete(&self.commands, &self.context, &self.input, MAX_SUGGESTIONS)
+        };
+        self.menu = match Menu::try_from(suggs) {
+            Ok(menu) if use_grid => Some(menu.with_grid(GRID_COLUMNS)),
+            Ok(menu) => Some(menu),
+            Err(_) => None,
+        };
+        self.schedule_preview();
     }
 }
 
-/// Ugh, because UI plugin methods take a mutable reference to themselves, we can't have part
-/// of them borrowed while calling those methods. So this is a workaround.
-fn with_plugins<F>(ui: &mut UserInterface, f: F) -> Result<(), Error>
-where
-    F: FnOnce(&mut UserInterface, &mut Vec<Box<dyn UserInterfacePlugin>>) -> Result<(), Error>,
-{
-    let mut plugins = match ui.plugins.take() {
-        Some(plugins) => plugins,
-        None => return Err(Error::new("plugins are in use")),
-    };
-    f(ui, &mut plugins)?;
-    ui.plugins = Some(plugins);
-    Ok(())
+fn cancel_command_name(task_name: &str) -> String {
+    format!("cancel {}", task_name)
 }
@@ -4,12 +4,22 @@ use winapi::shared::windef::POINT;
 use winapi::um::handleapi::CloseHandle;
 use winapi::um::processthreadsapi::OpenProcess;
 use winapi::um::psapi::GetModuleFileNameExW;
+use winapi::um::winbase::{
+    GlobalAlloc, GlobalFree, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE,
+};
 use winapi::um::winnt::{PROCESS_QUERY_INFORMATION, PROCESS_VM_READ};
 use winapi::um::winuser::{self, GetForegroundWindow, GetWindowTextW, GetWindowThreadProcessId};
+use winapi::um::winuser::{
+    CloseClipboard, EmptyClipboard, EnumClipboardFormats, GetClipboardData,
+    GetClipboardSequenceNumber, OpenClipboard, SetClipboardData, CF_BITMAP, CF_DSPBITMAP,
+    CF_DSPENHMETAFILE, CF_DSPMETAFILEPICT, CF_ENHMETAFILE, CF_METAFILEPICT, CF_OWNERDISPLAY,
+    CF_PALETTE, CF_UNICODETEXT,
+};
 use winapi::um::winuser::{
     GetKeyState, GetSystemMetrics, INPUT_u, SendInput, INPUT, INPUT_KEYBOARD, KEYEVENTF_KEYUP,
     KEYEVENTF_UNICODE, MSG, SM_CXSCREEN, SM_CYSCREEN, VK_CAPITAL,
 };
+use winapi::um::winuser::{KillTimer, SetTimer};
 
 use crate::system::{KeyDirection, VirtualKey};
 
@@ -20,6 +30,11 @@ const VK_9: i32 = 0x39;
 const VK_A: i32 = 0x41;
 const VK_Z: i32 = 0x5a;
 
+/// Put in the `dwExtraInfo` of every key event Enso synthesizes, so that the
+/// keyboard hook can tell them apart from ones synthesized by other programs,
+/// like remote desktop clients and on-screen keyboards, which are real input.
+pub const ENSO_KEY_EVENT_MARKER: usize = 0x454E_534F;
+
 pub fn create_blank_msg() -> MSG {
     MSG {
         hwnd: null_mut(),
@@ -87,6 +102,7 @@ fn send_keypress(vk: i32, direction: KeyDirection) -> Result<(), Error> {
             KeyDirection::Up => KEYEVENTF_KEYUP,
             KeyDirection::Down => 0,
         };
+        ki.dwExtraInfo = ENSO_KEY_EVENT_MARKER;
         let mut inp = INPUT {
             type_: INPUT_KEYBOARD,
             u,
@@ -99,6 +115,23 @@ fn send_keypress(vk: i32, direction: KeyDirection) -> Result<(), Error> {
     Ok(())
 }
 
+/// Starts a timer that posts `WM_TIMER` to the current thread's message queue
+/// every given number of milliseconds, waking up its event loop, and returns
+/// its ID. If the ID of an existing timer is given, it's restarted instead.
+pub fn set_thread_timer(id: Option<usize>, ms: u32) -> Result<usize, Error> {
+    let id = unsafe { SetTimer(null_mut(), id.unwrap_or(0), ms, None) };
+    if id == 0 {
+        return Err(Error::from_winapi());
+    }
+    Ok(id)
+}
+
+pub fn kill_thread_timer(id: usize) {
+    if unsafe { KillTimer(null_mut(), id) } == 0 {
+        println!("KillTimer() failed!");
+    }
+}
+
 pub fn disable_caps_lock() -> Result<(), Error> {
     let curr_state = unsafe { GetKeyState(VK_CAPITAL) };
 
@@ -138,22 +171,39 @@ pub fn disable_caps_lock() -> Result<(), Error> {
 
 pub fn send_unicode_keypress(value: &str) -> Result<(), Error> {
     // https://stackoverflow.com/a/22308727/2422398
+    //
+    // All the key events are sent at once, so that nothing else can be typed
+    // in the middle of a character made of several code points, like an emoji
+    // ZWJ sequence or one outside the Basic Multilingual Plane.
+    let mut inputs = vec![];
     for ch in value.encode_utf16() {
-        unsafe {
-            let mut u: INPUT_u = Default::default();
-            let mut ki = u.ki_mut();
-            ki.wScan = ch;
-            ki.dwFlags = KEYEVENTF_UNICODE;
-            let mut inp = INPUT {
-                type_: INPUT_KEYBOARD,
-                u,
-            };
-            let result = SendInput(1, &mut inp, std::mem::size_of_val(&inp) as i32);
-            if result != 1 {
-                return Err(Error::WindowsAPIGeneric);
+        for flags in [KEYEVENTF_UNICODE, KEYEVENTF_UNICODE | KEYEVENTF_KEYUP] {
+            unsafe {
+                let mut u: INPUT_u = Default::default();
+                let ki = u.ki_mut();
+                ki.wScan = ch;
+                ki.dwFlags = flags;
+                ki.dwExtraInfo = ENSO_KEY_EVENT_MARKER;
+                inputs.push(INPUT {
+                    type_: INPUT_KEYBOARD,
+                    u,
+                });
             }
         }
     }
+    if inputs.is_empty() {
+        return Ok(());
+    }
+    let result = unsafe {
+        SendInput(
+            inputs.len() as u32,
+            inputs.as_mut_ptr(),
+            std::mem::size_of::<INPUT>() as i32,
+        )
+    };
+    if result as usize != inputs.len() {
+        return Err(Error::WindowsAPIGeneric);
+    }
     Ok(())
 }
 
@@ -224,6 +274,149 @@ pub fn get_foreground_window_name() -> Result<String, Error> {
     Ok(name)
 }
 
+/// Keeps the clipboard open for as long as it exists.
+struct OpenedClipboard;
+
+impl OpenedClipboard {
+    fn open() -> Result<Self, Error> {
+        if unsafe { OpenClipboard(null_mut()) } == 0 {
+            return Err(Error::from_winapi());
+        }
+        Ok(OpenedClipboard)
+    }
+}
+
+impl Drop for OpenedClipboard {
+    fn drop(&mut self) {
+        if unsafe { CloseClipboard() } == 0 {
+            println!("Warning: Couldn't close clipboard!");
+        }
+    }
+}
+
+pub fn get_clipboard_text() -> Result<Option<String>, Error> {
+    let _clipboard = OpenedClipboard::open()?;
+    let handle = unsafe { GetClipboardData(CF_UNICODETEXT) };
+    if handle.is_null() {
+        return Ok(None);
+    }
+    let ptr = unsafe { GlobalLock(handle) } as *const u16;
+    if ptr.is_null() {
+        return Err(Error::from_winapi());
+    }
+    let mut len = 0;
+    while unsafe { *ptr.add(len) } != 0 {
+        len += 1;
+    }
+    let text = String::from_utf16_lossy(unsafe { std::slice::from_raw_parts(ptr, len) });
+    unsafe {
+        GlobalUnlock(handle);
+    }
+    Ok(Some(text))
+}
+
+/// Returns a number that changes whenever the clipboard's contents do.
+pub fn get_clipboard_sequence_number() -> u32 {
+    unsafe { GetClipboardSequenceNumber() }
+}
+
+pub fn set_clipboard_text(text: &str) -> Result<(), Error> {
+    let data: Vec<u8> = text
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .flat_map(u16::to_ne_bytes)
+        .collect();
+    let _clipboard = OpenedClipboard::open()?;
+    if unsafe { EmptyClipboard() } == 0 {
+        return Err(Error::from_winapi());
+    }
+    set_clipboard_data(CF_UNICODETEXT, &data)
+}
+
+/// Puts a copy of the given data on the clipboard, which must already be open.
+fn set_clipboard_data(format: u32, data: &[u8]) -> Result<(), Error> {
+    let handle = unsafe { GlobalAlloc(GMEM_MOVEABLE, data.len()) };
+    if handle.is_null() {
+        return Err(Error::from_winapi());
+    }
+    unsafe {
+        let ptr = GlobalLock(handle) as *mut u8;
+        if ptr.is_null() {
+            GlobalFree(handle);
+            return Err(Error::from_winapi());
+        }
+        std::ptr::copy_nonoverlapping(data.as_ptr(), ptr, data.len());
+        GlobalUnlock(handle);
+
+        // If this succeeds, the system owns the memory, so we shouldn't free it.
+        if SetClipboardData(format, handle).is_null() {
+            GlobalFree(handle);
+            return Err(Error::from_winapi());
+        }
+    }
+    Ok(())
+}
+
+// These formats are handles to GDI objects rather than memory we can copy,
+// so they can't be saved. Windows usually offers bitmaps as `CF_DIB` too,
+// which can be.
+const UNSAVEABLE_CLIPBOARD_FORMATS: [u32; 8] = [
+    CF_BITMAP,
+    CF_METAFILEPICT,
+    CF_PALETTE,
+    CF_ENHMETAFILE,
+    CF_OWNERDISPLAY,
+    CF_DSPBITMAP,
+    CF_DSPMETAFILEPICT,
+    CF_DSPENHMETAFILE,
+];
+
+/// A copy of everything that was on the clipboard, so that it can be put
+/// back after Enso has used the clipboard for something else.
+pub struct SavedClipboard {
+    formats: Vec<(u32, Vec<u8>)>,
+}
+
+pub fn save_clipboard() -> Result<SavedClipboard, Error> {
+    let _clipboard = OpenedClipboard::open()?;
+    let mut formats = vec![];
+    let mut format = 0;
+    loop {
+        format = unsafe { EnumClipboardFormats(format) };
+        if format == 0 {
+            break;
+        }
+        if UNSAVEABLE_CLIPBOARD_FORMATS.contains(&format) {
+            continue;
+        }
+        let handle = unsafe { GetClipboardData(format) };
+        if handle.is_null() {
+            continue;
+        }
+        let ptr = unsafe { GlobalLock(handle) } as *const u8;
+        if ptr.is_null() {
+            continue;
+        }
+        let data = unsafe { std::slice::from_raw_parts(ptr, GlobalSize(handle)) }.to_vec();
+        unsafe {
+            GlobalUnlock(handle);
+        }
+        formats.push((format, data));
+    }
+    Ok(SavedClipboard { formats })
+}
+
+pub fn restore_clipboard(saved: &SavedClipboard) -> Result<(), Error> {
+    let _clipboard = OpenedClipboard::open()?;
+    if unsafe { EmptyClipboard() } == 0 {
+        return Err(Error::from_winapi());
+    }
+    for (format, data) in &saved.formats {
+        set_clipboard_data(*format, data)?;
+    }
+    Ok(())
+}
+
 #[test]
 fn test_get_primary_screen_size() {
     assert!(get_primary_screen_size().is_ok());
@@ -234,24 +427,88 @@ fn test_disable_caps_lock() {
     assert!(disable_caps_lock().is_ok());
 }
 
-pub fn vkey_to_char(vk_code: i32) -> Option<char> {
+/// Converts the given virtual key code to the character it types, assuming a
+/// US keyboard layout.
+pub fn vkey_to_char(vk_code: i32, shift: bool) -> Option<char> {
     // TODO: These virtual key codes are actually just ASCII codes, we could
     // probably accomplish this better with e.g. `std::char::is_ascii_control`.
     // or something.
-    match vk_code {
-        VK_0..=VK_9 | VK_A..=VK_Z => Some(char::from(vk_code as u8)),
-        winuser::VK_SPACE => Some(' '),
-        _ => None,
+    let (unshifted, shifted) = match vk_code {
+        VK_0..=VK_9 => {
+            let digit = vk_code as u8;
+            (
+                char::from(digit),
+                b")!@#$%^&*("[(digit - VK_0 as u8) as usize] as char,
+            )
+        }
+        VK_A..=VK_Z => {
+            let letter = char::from(vk_code as u8);
+            (letter.to_ascii_lowercase(), letter)
+        }
+        winuser::VK_SPACE => (' ', ' '),
+        winuser::VK_OEM_1 => (';', ':'),
+        winuser::VK_OEM_PLUS => ('=', '+'),
+        winuser::VK_OEM_COMMA => (',', '<'),
+        winuser::VK_OEM_MINUS => ('-', '_'),
+        winuser::VK_OEM_PERIOD => ('.', '>'),
+        winuser::VK_OEM_2 => ('/', '?'),
+        winuser::VK_OEM_3 => ('`', '~'),
+        winuser::VK_OEM_4 => ('[', '{'),
+        winuser::VK_OEM_5 => ('\\', '|'),
+        winuser::VK_OEM_6 => (']', '}'),
+        winuser::VK_OEM_7 => ('\'', '"'),
+        _ => return None,
+    };
+    Some(if shift { shifted } else { unshifted })
+}
+
+/// Updates the quasimode's input for the given keypress, keeping the case of
+/// what was typed, since e.g. digraphs and LaTeX names depend on it. Returns
+/// whether the input changed.
+pub fn edit_input(input: &mut String, vk_code: i32, shift: bool) -> bool {
+    if vk_code == winuser::VK_BACK {
+        return input.pop().is_some();
+    }
+    match vkey_to_char(vk_code, shift) {
+        Some(ch) => {
+            input.push(ch);
+            true
+        }
+        None => false,
     }
 }
 
 #[test]
 fn test_vkey_to_char() {
-    assert_eq!(vkey_to_char(VK_0), Some('0'));
-    assert_eq!(vkey_to_char(VK_0 + 3), Some('3'));
-    assert_eq!(vkey_to_char(VK_9), Some('9'));
-    assert_eq!(vkey_to_char(VK_A), Some('A'));
-    assert_eq!(vkey_to_char(VK_A + 3), Some('D'));
-    assert_eq!(vkey_to_char(VK_Z), Some('Z'));
-    assert_eq!(vkey_to_char(winuser::VK_F1), None);
+    assert_eq!(vkey_to_char(VK_0, false), Some('0'));
+    assert_eq!(vkey_to_char(VK_0 + 3, false), Some('3'));
+    assert_eq!(vkey_to_char(VK_9, false), Some('9'));
+    assert_eq!(vkey_to_char(VK_A, false), Some('a'));
+    assert_eq!(vkey_to_char(VK_A + 3, false), Some('d'));
+    assert_eq!(vkey_to_char(VK_Z, false), Some('z'));
+    assert_eq!(vkey_to_char(winuser::VK_F1, false), None);
+}
+
+#[test]
+fn test_vkey_to_char_with_shift() {
+    assert_eq!(vkey_to_char(VK_0, true), Some(')'));
+    assert_eq!(vkey_to_char(VK_0 + 6, true), Some('^'));
+    assert_eq!(vkey_to_char(VK_A, true), Some('A'));
+    assert_eq!(vkey_to_char(winuser::VK_OEM_5, false), Some('\\'));
+    assert_eq!(vkey_to_char(winuser::VK_OEM_5, true), Some('|'));
+    assert_eq!(vkey_to_char(winuser::VK_F1, true), None);
+}
+
+#[test]
+fn test_edit_input_keeps_case() {
+    let mut input = String::new();
+    assert!(edit_input(&mut input, VK_A, true));
+    assert!(edit_input(&mut input, winuser::VK_OEM_1, true));
+    assert!(edit_input(&mut input, VK_A, false));
+    assert!(!edit_input(&mut input, winuser::VK_F1, false));
+    assert_eq!(input, "A:a");
+    assert!(edit_input(&mut input, winuser::VK_BACK, false));
+    assert_eq!(input, "A:");
+    input.clear();
+    assert!(!edit_input(&mut input, winuser::VK_BACK, false));
 }